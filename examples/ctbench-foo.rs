@@ -25,7 +25,7 @@ fn arith(runner: &mut CtRunner, rng: &mut BenchRng) {
         }
     }
 
-    for (u, class) in inputs.into_iter().zip(classes.into_iter()) {
+    for (u, class) in inputs.into_iter().zip(classes) {
         // Time some random arithmetic operations
         runner.run_one(class, || ((u + 10) / 6) << 5);
     }
@@ -60,7 +60,7 @@ fn vec_eq(runner: &mut CtRunner, rng: &mut BenchRng) {
         }
     }
 
-    for (class, (u, v)) in classes.into_iter().zip(inputs.into_iter()) {
+    for (class, (u, v)) in classes.into_iter().zip(inputs) {
         // Now time how long it takes to do a vector comparison
         runner.run_one(class, || u == v);
     }
@@ -0,0 +1,156 @@
+//! Offline analysis of raw samples exported with `--out`. This runs the same statistics as a live
+//! run, so old data can be re-examined whenever the statistics improve.
+
+use crate::{
+    baseline, csv,
+    ctbench::{BenchName, BenchOpts, BenchReport, Class, DEFAULT_T_THRESHOLD},
+    report::Reporter,
+    stats,
+};
+
+use std::{
+    collections::HashMap,
+    io::{self, BufRead},
+    mem,
+};
+
+/// The statistics of a single bench, as its samples are read back
+struct BenchState {
+    name: BenchName,
+    seed: u64,
+    ctx: Option<stats::CtCtx>,
+    // The batch currently being read, and its samples
+    pending_batch: usize,
+    pending: (Vec<u64>, Vec<u64>),
+    // The report after every batch so far
+    reports: Vec<BenchReport>,
+}
+
+impl BenchState {
+    /// Feeds the pending batch, if any, into the statistics
+    fn flush(&mut self, opts: &BenchOpts) {
+        if self.pending.0.is_empty() && self.pending.1.is_empty() {
+            return;
+        }
+
+        let samples = mem::take(&mut self.pending);
+        let (summ, ctx) = stats::update_ct_stats(
+            self.ctx.take(),
+            &opts.crop,
+            &opts.dist_tests,
+            opts.correction,
+            opts.bootstrap.is_some(),
+            &samples,
+        );
+        let batches = self.reports.len() + 1;
+        let threshold = opts.threshold.unwrap_or(DEFAULT_T_THRESHOLD);
+        let report = BenchReport::new(
+            self.name, self.seed, batches, threshold, opts.alpha, summ, &ctx,
+        );
+        self.ctx = Some(ctx);
+        self.reports.push(report);
+    }
+}
+
+/// Reads raw samples in the CSV format written by `--out`, and runs them through the same
+/// statistics as a live run. Samples are grouped by bench name and class, and fed into the
+/// statistics one batch at a time, in the order the batches were collected.
+///
+/// The statistics and verdicts are configured by `opts` as they would be in a live run, so to
+/// reproduce a live run, these should match the options it was run with. Only benches whose name
+/// contains `opts.filter` (if given) are analyzed. The options that only concern running benches,
+/// i.e., `continuous`, `file_out`, `timer`, `format`, the budgets, `min_effect`, and the checkpoint
+/// options, are ignored. If `opts.bootstrap` is set, the final report of each bench includes
+/// bootstrap confidence intervals, which are reproducible as long as the seed is the same. If
+/// `opts.baseline` is set, the final reports are compared with the baseline, and if
+/// `opts.save_baseline` is set, they are saved as a baseline.
+///
+/// Once the input is exhausted, events are sent to `reporter` as they would be in a live run, with
+/// an intermediate result for every batch but the last of benches that have more than one. Returns
+/// a report for each bench, in the order they first appear in the input.
+///
+/// Bench names are leaked, since [`BenchName`] only holds `&'static str`s.
+pub fn analyze_csv<R: BufRead>(
+    input: R,
+    opts: &BenchOpts,
+    reporter: &mut dyn Reporter,
+) -> io::Result<Vec<BenchReport>> {
+    opts.validate()?;
+    let filter = opts.filter.as_deref();
+    let mut benches: Vec<BenchState> = Vec::new();
+
+    for (i, line) in input.lines().enumerate() {
+        let line = line?;
+        let record = csv::parse_line(&line).map_err(|e| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", i + 1, e))
+        })?;
+        let record = match record {
+            Some(r) if filter.is_none_or(|f| r.name.contains(f)) => r,
+            _ => continue,
+        };
+
+        // Find the bench this sample belongs to, or start a new one
+        let idx = match benches.iter().position(|b| b.name.0 == record.name) {
+            Some(idx) => idx,
+            None => {
+                let name = BenchName(Box::leak(record.name.to_string().into_boxed_str()));
+                benches.push(BenchState {
+                    name,
+                    seed: record.seed,
+                    ctx: None,
+                    pending_batch: record.batch,
+                    pending: (Vec::new(), Vec::new()),
+                    reports: Vec::new(),
+                });
+                benches.len() - 1
+            }
+        };
+        let bench = &mut benches[idx];
+
+        // A new batch has started, so the previous one is complete
+        if record.batch != bench.pending_batch {
+            bench.flush(opts);
+            bench.pending_batch = record.batch;
+        }
+
+        match record.class {
+            Class::Left => bench.pending.0.push(record.measurement),
+            Class::Right => bench.pending.1.push(record.measurement),
+        }
+    }
+
+    let names: Vec<BenchName> = benches.iter().map(|b| b.name).collect();
+    let baselines = match opts.baseline {
+        Some(ref name) => baseline::load(&opts.baseline_dir(), name, &names)?,
+        None => HashMap::new(),
+    };
+    reporter.run_start(&names, false)?;
+
+    let mut reports = Vec::new();
+    for mut bench in benches {
+        bench.flush(opts);
+        let mut last = match bench.reports.pop() {
+            Some(r) => r,
+            None => continue,
+        };
+        if let Some(ctx) = bench.ctx.as_ref() {
+            let baseline = baselines.get(bench.name.0);
+            last.finalize(ctx, opts.bootstrap.as_ref(), baseline);
+        }
+
+        reporter.seed(bench.name, bench.seed)?;
+        reporter.bench_start(bench.name)?;
+        for report in &bench.reports {
+            reporter.intermediate_result(report)?;
+        }
+        reporter.bench_finish(&last)?;
+        reports.push(last);
+    }
+
+    if let Some(ref name) = opts.save_baseline {
+        baseline::save(&opts.baseline_dir(), name, &reports)?;
+    }
+
+    reporter.run_finish(&reports)?;
+    Ok(reports)
+}
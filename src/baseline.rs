@@ -0,0 +1,285 @@
+//! Saved baselines, for comparing the results of a run with those of an earlier one. A baseline is
+//! a directory under the baseline directory, holding a file per bench, e.g.,
+//! `target/dudect/main/vec_eq.baseline`. The first line of each file identifies the crate version:
+//!
+//! ```text
+//! # dudect-bencher 0.7.0 baseline
+//! summary 4055897f6a35d2d6 3fc4845c4d5b5a0a 4014000000000000 - LEAK
+//! ctx 3 101 holm - false
+//! ...
+//! ```
+//!
+//! The `summary` line gives the bench's max t, max tau, and threshold, the significance level it
+//! was judged at (or `-`), and its verdict. Floats are written as the hex of their bits. It is
+//! followed by the bench's statistics, in the format of a checkpoint. The statistics are only read
+//! back by the version of the crate that wrote them.
+
+use crate::{
+    ctbench::{BenchName, BenchReport, Verdict},
+    stats::CtCtx,
+};
+
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+/// The result of a bench in a saved baseline
+pub(crate) struct Baseline {
+    name: String,
+    max_t: f64,
+    max_tau: f64,
+    verdict: Verdict,
+    ctx: Option<Arc<CtCtx>>,
+}
+
+/// How the final result of a bench compares with its result in a saved baseline. The deltas compare
+/// magnitudes, so a positive delta means the bench looks leakier than it did in the baseline.
+#[derive(Clone, Debug)]
+pub struct BaselineComparison {
+    /// The name of the baseline
+    pub baseline: String,
+    /// The bench's max t in the baseline
+    pub max_t: f64,
+    /// The bench's max tau in the baseline
+    pub max_tau: f64,
+    /// The bench's verdict in the baseline
+    pub verdict: Verdict,
+    /// The change in the magnitude of max t since the baseline
+    pub max_t_delta: f64,
+    /// The change in the magnitude of max tau since the baseline
+    pub max_tau_delta: f64,
+    /// Whether the bench passed in the baseline, and leaks now
+    pub regressed: bool,
+    /// The statistics the bench accumulated in the baseline, if they were saved by this version of
+    /// the crate
+    pub ctx: Option<Arc<CtCtx>>,
+}
+
+impl Baseline {
+    /// Compares `report` with this baseline
+    pub(crate) fn compare(&self, report: &BenchReport) -> BaselineComparison {
+        BaselineComparison {
+            baseline: self.name.clone(),
+            max_t: self.max_t,
+            max_tau: self.max_tau,
+            verdict: self.verdict,
+            max_t_delta: report.summary.max_t.abs() - self.max_t.abs(),
+            max_tau_delta: report.summary.max_tau.abs() - self.max_tau.abs(),
+            regressed: self.verdict == Verdict::Pass && report.verdict == Verdict::Leak,
+            ctx: self.ctx.clone(),
+        }
+    }
+}
+
+/// Returns the first line of a baseline file written by this version of the crate
+fn header() -> String {
+    format!("# dudect-bencher {} baseline", env!("CARGO_PKG_VERSION"))
+}
+
+/// Returns the path of the file holding the result of `bench` in the baseline at `path`. Characters
+/// other than ASCII letters, digits, `_`, and `-`, like the colons of a module path, are escaped as
+/// `%` followed by the hex of their UTF-8 bytes, so that distinct benches get distinct files.
+fn bench_path(path: &Path, bench: BenchName) -> PathBuf {
+    let mut file_name = String::new();
+    for c in bench.0.chars() {
+        if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+            file_name.push(c);
+        } else {
+            let mut buf = [0u8; 4];
+            for b in c.encode_utf8(&mut buf).bytes() {
+                file_name.push_str(&format!("%{:02X}", b));
+            }
+        }
+    }
+    path.join(file_name + ".baseline")
+}
+
+/// Saves the final result of every bench in `reports` as the baseline `name` in `dir`. The results
+/// of benches that are already in the baseline are replaced, and those of other benches are kept.
+pub(crate) fn save(dir: &Path, name: &str, reports: &[BenchReport]) -> io::Result<()> {
+    let path = dir.join(name);
+
+    // Names that only differ in case would share a file on case-insensitive file systems
+    let mut file_names: HashMap<String, BenchName> = HashMap::new();
+    for report in reports {
+        let file_name = bench_path(&path, report.name)
+            .to_string_lossy()
+            .to_lowercase();
+        if let Some(other) = file_names.insert(file_name, report.name) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "cannot save both '{}' and '{}' in a baseline, as their names only differ in \
+                    case",
+                    other.0, report.name.0
+                ),
+            ));
+        }
+    }
+
+    fs::create_dir_all(&path).map_err(|e| {
+        io::Error::new(
+            e.kind(),
+            format!("could not create '{}': {}", path.display(), e),
+        )
+    })?;
+
+    for report in reports {
+        let file_path = bench_path(&path, report.name);
+        let file = File::create(&file_path).map_err(|e| {
+            io::Error::new(
+                e.kind(),
+                format!(
+                    "could not open '{}' for writing: {}",
+                    file_path.display(),
+                    e
+                ),
+            )
+        })?;
+        let mut out = BufWriter::new(file);
+
+        writeln!(out, "{}", header())?;
+        writeln!(
+            out,
+            "summary {:016x} {:016x} {:016x} {} {}",
+            report.summary.max_t.to_bits(),
+            report.summary.max_tau.to_bits(),
+            report.threshold.to_bits(),
+            report
+                .alpha
+                .map_or("-".to_string(), |a| format!("{:016x}", a.to_bits())),
+            report.verdict
+        )?;
+        if let Some(ref ctx) = report.ctx {
+            ctx.write_to(&mut out)?;
+        }
+        out.flush()?;
+    }
+
+    Ok(())
+}
+
+/// Loads the result of each of `benches` from the baseline `name` in `dir`. Benches that aren't in
+/// the baseline are left out. Fails if there is no such baseline.
+pub(crate) fn load(
+    dir: &Path,
+    name: &str,
+    benches: &[BenchName],
+) -> io::Result<HashMap<&'static str, Baseline>> {
+    let path = dir.join(name);
+    if !path.is_dir() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!(
+                "there is no baseline named '{}' in '{}'",
+                name,
+                dir.display()
+            ),
+        ));
+    }
+
+    let mut baselines = HashMap::new();
+    for &bench in benches {
+        let file_path = bench_path(&path, bench);
+        let file = match File::open(&file_path) {
+            Ok(f) => f,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => {
+                return Err(io::Error::new(
+                    e.kind(),
+                    format!(
+                        "could not open '{}' for reading: {}",
+                        file_path.display(),
+                        e
+                    ),
+                ))
+            }
+        };
+        let baseline = parse(BufReader::new(file), name).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid baseline '{}': {}", file_path.display(), e),
+            )
+        })?;
+        baselines.insert(bench.0, baseline);
+    }
+
+    Ok(baselines)
+}
+
+/// Parses the result of a bench in the baseline `name`
+fn parse<R: BufRead>(input: R, name: &str) -> Result<Baseline, String> {
+    let mut lines = input.lines().peekable();
+    let mut next_line = || {
+        lines
+            .next()
+            .ok_or_else(|| "unexpected end of file".to_string())?
+            .map_err(|e| e.to_string())
+    };
+
+    let first = next_line()?;
+    if !first.starts_with("# dudect-bencher ") || !first.ends_with(" baseline") {
+        return Err("it is not a baseline".to_string());
+    }
+    let same_version = first == header();
+
+    let line = next_line()?;
+    let fields: Vec<&str> = line.split(' ').collect();
+    if fields.len() != 6 || fields[0] != "summary" {
+        return Err(format!("expected a 'summary' line, found '{}'", line));
+    }
+    let float = |s: &str| {
+        u64::from_str_radix(s, 16)
+            .map(f64::from_bits)
+            .map_err(|e| format!("invalid float '{}': {}", s, e))
+    };
+    let verdict = match fields[5] {
+        "PASS" => Verdict::Pass,
+        "LEAK" => Verdict::Leak,
+        v => return Err(format!("unknown verdict '{}'", v)),
+    };
+    let mut baseline = Baseline {
+        name: name.to_string(),
+        max_t: float(fields[1])?,
+        max_tau: float(fields[2])?,
+        verdict,
+        ctx: None,
+    };
+
+    // The format of the statistics may change between versions, so only read them if they're ours
+    if same_version && lines.peek().is_some() {
+        baseline.ctx = Some(Arc::new(CtCtx::read_checkpoint(&mut lines)?));
+    }
+
+    Ok(baseline)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bench_paths_are_distinct() {
+        let dir = Path::new("main");
+        assert_eq!(
+            bench_path(dir, BenchName("vec_eq")),
+            dir.join("vec_eq.baseline")
+        );
+        assert_eq!(
+            bench_path(dir, BenchName("a::b")),
+            dir.join("a%3A%3Ab.baseline")
+        );
+        assert_ne!(
+            bench_path(dir, BenchName("a::b")),
+            bench_path(dir, BenchName("a_b"))
+        );
+        assert_ne!(
+            bench_path(dir, BenchName("a%3Ab")),
+            bench_path(dir, BenchName("a:b"))
+        );
+    }
+}
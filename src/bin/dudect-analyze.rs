@@ -0,0 +1,195 @@
+//! Re-runs the statistics over raw samples exported with `--out`, and prints the same summaries as
+//! a live run
+
+use dudect_bencher::{
+    analyze::analyze_csv,
+    cli::{parse_seed, validate_baseline_name, validate_seed},
+    ctbench::{BenchOpts, Verdict},
+    BootstrapConfig, CropScheme, DistTest,
+};
+
+use std::{
+    fs::File,
+    io::{self, BufReader},
+    path::PathBuf,
+    process,
+};
+
+use clap::{App, Arg};
+
+fn main() {
+    let matches = App::new("dudect-analyze")
+        .about("Analyzes raw dudect-bencher samples from a CSV file written with --out")
+        .arg_from_usage("<FILE> 'The CSV file to analyze, or - to read from stdin'")
+        .arg_from_usage(
+            "--filter [BENCH] \
+            'Only analyze the benchmarks whose name contains BENCH'",
+        )
+        .arg(
+            Arg::from_usage(
+                "--threshold [T] \
+                'Sets the t-value above which a bench is considered to leak. Defaults to 5'",
+            )
+            .validator(|s| s.parse::<f64>().map(|_| ()).map_err(|e| e.to_string())),
+        )
+        .arg(
+            Arg::from_usage(
+                "--format [FORMAT] \
+                'Sets the output format. Defaults to pretty. json prints an array of final \
+                results only; per-batch results need jsonl'",
+            )
+            .possible_values(&["pretty", "json", "jsonl"]),
+        )
+        .arg(
+            Arg::from_usage(
+                "--crop [SCHEME] \
+                'Sets the percentiles samples are cropped at: exp:N, linear:N, a comma-separated \
+                list of percentiles in (0, 1], or none. Defaults to exp:100'",
+            )
+            .validator(|s| s.parse::<CropScheme>().map(|_| ())),
+        )
+        .arg_from_usage(
+            "--two-sided-crop \
+            'Crops both tails of the distributions, keeping the central samples'",
+        )
+        .arg(
+            Arg::from_usage(
+                "--crop-reservoir [N] \
+                'Re-estimates the crop thresholds after 2, 4, 8, ... batches from a random sample \
+                of up to N of the samples seen so far'",
+            )
+            .validator(|s| match s.parse::<usize>() {
+                Ok(n) if n > 0 => Ok(()),
+                Ok(_) => Err("N must be positive".to_string()),
+                Err(e) => Err(e.to_string()),
+            }),
+        )
+        .arg(
+            Arg::from_usage(
+                "--dist-tests [TESTS] \
+                'Runs the given comma-separated nonparametric tests over every sample, in addition \
+                to the t-tests: ks (Kolmogorov-Smirnov), ad (Anderson-Darling), and mw \
+                (Mann-Whitney U)'",
+            )
+            .validator(|s| {
+                s.split(',')
+                    .try_for_each(|t| t.parse::<DistTest>().map(|_| ()))
+            }),
+        )
+        .arg(
+            Arg::from_usage(
+                "--correction [METHOD] \
+                'Sets how the p-values of the t-tests are corrected for multiple comparisons. \
+                Defaults to holm'",
+            )
+            .possible_values(&["none", "bonferroni", "holm"]),
+        )
+        .arg(
+            Arg::from_usage(
+                "--alpha [P] \
+                'Judges benches by their corrected p-value instead of their t-values: a bench \
+                leaks if it is below P'",
+            )
+            .validator(|s| match s.parse::<f64>() {
+                Ok(x) if x > 0.0 && x < 1.0 => Ok(()),
+                Ok(_) => Err("P must be in (0, 1)".to_string()),
+                Err(e) => Err(e.to_string()),
+            }),
+        )
+        .arg(
+            Arg::from_usage(
+                "--bootstrap [N] \
+                'Computes bootstrap confidence intervals for the effect size of every bench from N \
+                resamples'",
+            )
+            .validator(|s| match s.parse::<usize>() {
+                Ok(n) if n > 0 => Ok(()),
+                Ok(_) => Err("N must be positive".to_string()),
+                Err(e) => Err(e.to_string()),
+            }),
+        )
+        .arg(
+            Arg::from_usage(
+                "--bootstrap-seed [SEED] \
+                'Seeds the bootstrap with the hex number SEED, rather than the bench seed'",
+            )
+            .requires("bootstrap")
+            .validator(validate_seed),
+        )
+        .arg(
+            Arg::from_usage(
+                "--save-baseline [NAME] \
+                'Saves the result of every bench as the baseline NAME'",
+            )
+            .validator(validate_baseline_name),
+        )
+        .arg(
+            Arg::from_usage(
+                "--baseline [NAME] \
+                'Compares the result of every bench with the baseline NAME'",
+            )
+            .validator(validate_baseline_name),
+        )
+        .arg_from_usage(
+            "--baseline-dir [DIR] \
+            'Sets the directory that baselines are saved in. Defaults to target/dudect'",
+        )
+        .get_matches();
+
+    let mut opts = BenchOpts {
+        filter: matches.value_of("filter").map(|s| s.to_string()),
+        threshold: matches.value_of("threshold").map(|t| t.parse().unwrap()),
+        alpha: matches.value_of("alpha").map(|a| a.parse().unwrap()),
+        save_baseline: matches.value_of("save-baseline").map(|s| s.to_string()),
+        baseline: matches.value_of("baseline").map(|s| s.to_string()),
+        baseline_dir: matches.value_of("baseline-dir").map(PathBuf::from),
+        ..BenchOpts::default()
+    };
+    if let Some(format) = matches.value_of("format") {
+        opts.format = format.parse().unwrap();
+    }
+    if let Some(scheme) = matches.value_of("crop") {
+        opts.crop.scheme = scheme.parse().unwrap();
+    }
+    opts.crop.two_sided = matches.is_present("two-sided-crop");
+    opts.crop.reservoir_size = matches
+        .value_of("crop-reservoir")
+        .map(|n| n.parse().unwrap());
+    if let Some(tests) = matches.value_of("dist-tests") {
+        opts.dist_tests = tests.split(',').map(|t| t.parse().unwrap()).collect();
+    }
+    if let Some(correction) = matches.value_of("correction") {
+        opts.correction = correction.parse().unwrap();
+    }
+    if let Some(n) = matches.value_of("bootstrap") {
+        opts.bootstrap = Some(BootstrapConfig {
+            resamples: n.parse().unwrap(),
+            seed: matches
+                .value_of("bootstrap-seed")
+                .map(|s| parse_seed(s).unwrap()),
+            ..BootstrapConfig::default()
+        });
+    }
+    let mut reporter = opts.format.reporter();
+
+    let path = matches.value_of("FILE").unwrap();
+    let res = if path == "-" {
+        analyze_csv(io::stdin().lock(), &opts, &mut *reporter)
+    } else {
+        File::open(path)
+            .map_err(|e| io::Error::new(e.kind(), format!("could not open '{}': {}", path, e)))
+            .and_then(|f| analyze_csv(BufReader::new(f), &opts, &mut *reporter))
+    };
+
+    match res {
+        Ok(reports) => {
+            if reports.iter().any(|r| r.verdict == Verdict::Leak) {
+                process::exit(1);
+            }
+        }
+        Err(e) => {
+            eprintln!("error: {}", e);
+            process::exit(2);
+        }
+    }
+}
@@ -0,0 +1,356 @@
+//! The format that the state of a run is checkpointed in, so that the run can be resumed after the
+//! process restarts. The first line identifies the crate version, e.g.,
+//!
+//! ```text
+//! # dudect-bencher 0.7.0 checkpoint
+//! ```
+//!
+//! A checkpoint can only be resumed by the version that wrote it. The next lines give the master
+//! seed of the run in hex, the length in bytes of the `--out` file when the checkpoint was written
+//! (or `-` if there is none), the correction and distribution tests of the run, and the number of
+//! benches. They are followed by a block per bench, in the order they run:
+//!
+//! ```text
+//! master 000000006b6c816d
+//! out 48213
+//! options holm -
+//! benches 1
+//! bench 000000006b6c816d 12 3081723334 - vec_eq
+//! cropping exp:100 false -
+//! ctx 12 101 holm - false
+//! ...
+//! ```
+//!
+//! The `bench` line gives the bench's seed in hex, its number of batches, the time it has run for
+//! in nanoseconds, why it stopped (or `-` if it hasn't), and its name. The `cropping` line gives
+//! the bench's crop scheme, whether its crops are two-sided, and the size of its reservoir (or `-`
+//! if it has none). They are followed by the bench's statistics, or by `ctx -` if it hasn't run
+//! yet. The RNG of every batch is derived from the seed, so the number of batches is all it takes
+//! to restore the RNG. A run can only be resumed with the options that it was started with, since
+//! its statistics were accumulated under them.
+//!
+//! A resumed run truncates the `--out` file to the length in the checkpoint before it appends to
+//! it, so that the samples of batches that ran after the checkpoint was written, and are run again,
+//! aren't recorded twice.
+
+use crate::{
+    ctbench::{BenchName, StopReason},
+    stats::{Correction, CropConfig, CtCtx, DistTest},
+};
+
+use std::{
+    fs::{self, File},
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::Path,
+    time::Duration,
+};
+
+/// The state of a bench, as it's written to a checkpoint
+pub(crate) struct BenchState<'a> {
+    pub(crate) name: BenchName,
+    pub(crate) seed: u64,
+    pub(crate) batches: usize,
+    pub(crate) elapsed: Duration,
+    pub(crate) stop_reason: Option<StopReason>,
+    pub(crate) crop: &'a CropConfig,
+    pub(crate) ctx: Option<&'a CtCtx>,
+}
+
+/// The state of a run, as it's read back from a checkpoint
+pub(crate) struct SavedRun {
+    pub(crate) master_seed: u64,
+    // The length of the CSV output when the checkpoint was written, if there was any
+    pub(crate) csv_len: Option<u64>,
+    pub(crate) correction: Correction,
+    pub(crate) dist_tests: Vec<DistTest>,
+    pub(crate) benches: Vec<SavedBench>,
+}
+
+/// The state of a bench, as it's read back from a checkpoint
+pub(crate) struct SavedBench {
+    pub(crate) seed: u64,
+    pub(crate) batches: usize,
+    pub(crate) elapsed: Duration,
+    pub(crate) stop_reason: Option<StopReason>,
+    pub(crate) crop: CropConfig,
+    pub(crate) ctx: Option<CtCtx>,
+}
+
+/// Returns the first line of a checkpoint written by this version of the crate
+fn header() -> String {
+    format!("# dudect-bencher {} checkpoint", env!("CARGO_PKG_VERSION"))
+}
+
+/// Writes the master seed of a run, the length of its CSV output, its options, and the state of
+/// every bench to the file at `path`. The checkpoint is written to a temporary file first and then moved into
+/// place, so that a crash mid-write doesn't clobber the previous checkpoint.
+pub(crate) fn write(
+    path: &Path,
+    master_seed: u64,
+    csv_len: Option<u64>,
+    correction: Correction,
+    dist_tests: &[DistTest],
+    benches: &[BenchState],
+) -> io::Result<()> {
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+
+    let file = File::create(&tmp_path).map_err(|e| {
+        io::Error::new(
+            e.kind(),
+            format!(
+                "could not open '{}' for writing: {}",
+                Path::new(&tmp_path).display(),
+                e
+            ),
+        )
+    })?;
+    let mut out = BufWriter::new(file);
+
+    writeln!(out, "{}", header())?;
+    writeln!(out, "master {:016x}", master_seed)?;
+    match csv_len {
+        Some(len) => writeln!(out, "out {}", len)?,
+        None => writeln!(out, "out -")?,
+    }
+    writeln!(
+        out,
+        "options {} {}",
+        correction,
+        dist_tests_token(dist_tests)
+    )?;
+    writeln!(out, "benches {}", benches.len())?;
+    for bench in benches {
+        writeln!(
+            out,
+            "bench {:016x} {} {} {} {}",
+            bench.seed,
+            bench.batches,
+            bench.elapsed.as_nanos(),
+            bench.stop_reason.map_or("-", stop_reason_token),
+            bench.name.0
+        )?;
+        writeln!(
+            out,
+            "cropping {} {} {}",
+            bench.crop.scheme,
+            bench.crop.two_sided,
+            bench
+                .crop
+                .reservoir_size
+                .map_or("-".to_string(), |s| s.to_string())
+        )?;
+        match bench.ctx {
+            Some(ctx) => ctx.write_to(&mut out)?,
+            None => writeln!(out, "ctx -")?,
+        }
+    }
+
+    out.into_inner()?.sync_all()?;
+    fs::rename(&tmp_path, path)
+}
+
+/// Reads back the state of a run from the checkpoint at `path`. Fails if the checkpoint was written
+/// by another version of the crate, or for a list of benches other than `names`.
+pub(crate) fn read(path: &Path, names: &[BenchName]) -> io::Result<SavedRun> {
+    let file = File::open(path).map_err(|e| {
+        io::Error::new(
+            e.kind(),
+            format!("could not open '{}' for reading: {}", path.display(), e),
+        )
+    })?;
+    parse(BufReader::new(file), names).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("cannot resume from '{}': {}", path.display(), e),
+        )
+    })
+}
+
+/// Parses a checkpoint for the given list of benches
+fn parse<R: BufRead>(input: R, names: &[BenchName]) -> Result<SavedRun, String> {
+    let mut lines = input.lines().peekable();
+    let next_line = |lines: &mut dyn Iterator<Item = io::Result<String>>| {
+        lines
+            .next()
+            .ok_or_else(|| "unexpected end of file".to_string())?
+            .map_err(|e| e.to_string())
+    };
+
+    let first = next_line(&mut lines)?;
+    if first != header() {
+        return match first.strip_suffix(" checkpoint") {
+            Some(h) if h.starts_with("# dudect-bencher ") => Err(format!(
+                "it was written by {}, but this is version {}",
+                &h[2..],
+                env!("CARGO_PKG_VERSION")
+            )),
+            _ => Err("it is not a checkpoint".to_string()),
+        };
+    }
+
+    let master_line = next_line(&mut lines)?;
+    let master_seed = master_line
+        .strip_prefix("master ")
+        .and_then(|s| u64::from_str_radix(s, 16).ok())
+        .ok_or_else(|| format!("expected a 'master' line, found '{}'", master_line))?;
+
+    let out_line = next_line(&mut lines)?;
+    let csv_len = match out_line.strip_prefix("out ") {
+        Some("-") => None,
+        Some(len) => Some(
+            len.parse()
+                .map_err(|e| format!("invalid output length '{}': {}", len, e))?,
+        ),
+        None => return Err(format!("expected an 'out' line, found '{}'", out_line)),
+    };
+
+    let options_line = next_line(&mut lines)?;
+    let options: Vec<&str> = match options_line.strip_prefix("options ") {
+        Some(o) => o.split(' ').collect(),
+        None => {
+            return Err(format!(
+                "expected an 'options' line, found '{}'",
+                options_line
+            ))
+        }
+    };
+    if options.len() != 2 {
+        return Err(format!("invalid options '{}'", options_line));
+    }
+    let correction = options[0].parse()?;
+    let dist_tests = match options[1] {
+        "-" => Vec::new(),
+        tests => tests
+            .split(',')
+            .map(str::parse)
+            .collect::<Result<Vec<DistTest>, String>>()?,
+    };
+
+    let count_line = next_line(&mut lines)?;
+    let count: usize = count_line
+        .strip_prefix("benches ")
+        .and_then(|c| c.parse().ok())
+        .ok_or_else(|| format!("expected a 'benches' line, found '{}'", count_line))?;
+    if count != names.len() {
+        return Err(format!(
+            "it has {} benches, but {} were selected",
+            count,
+            names.len()
+        ));
+    }
+
+    let mut benches = Vec::with_capacity(count);
+    for name in names {
+        let line = next_line(&mut lines)?;
+        let fields: Vec<&str> = line.splitn(6, ' ').collect();
+        if fields.len() != 6 || fields[0] != "bench" {
+            return Err(format!("expected a 'bench' line, found '{}'", line));
+        }
+        if fields[5] != name.0 {
+            return Err(format!(
+                "it has bench '{}' where '{}' was selected",
+                fields[5], name.0
+            ));
+        }
+
+        let seed = u64::from_str_radix(fields[1], 16)
+            .map_err(|e| format!("invalid seed '{}': {}", fields[1], e))?;
+        let batches = fields[2]
+            .parse()
+            .map_err(|e| format!("invalid batch count '{}': {}", fields[2], e))?;
+        let elapsed = fields[3]
+            .parse()
+            .map(Duration::from_nanos)
+            .map_err(|e| format!("invalid elapsed time '{}': {}", fields[3], e))?;
+        let stop_reason = match fields[4] {
+            "-" => None,
+            token => Some(parse_stop_reason(token)?),
+        };
+
+        let line = next_line(&mut lines)?;
+        let fields: Vec<&str> = line.split(' ').collect();
+        if fields.len() != 4 || fields[0] != "cropping" {
+            return Err(format!("expected a 'cropping' line, found '{}'", line));
+        }
+        let crop = CropConfig {
+            scheme: fields[1].parse()?,
+            two_sided: fields[2]
+                .parse()
+                .map_err(|e| format!("invalid two-sidedness '{}': {}", fields[2], e))?,
+            reservoir_size: match fields[3] {
+                "-" => None,
+                size => Some(
+                    size.parse()
+                        .map_err(|e| format!("invalid reservoir size '{}': {}", size, e))?,
+                ),
+            },
+        };
+
+        let ctx = match lines.peek() {
+            Some(Ok(l)) if l == "ctx -" => {
+                lines.next();
+                None
+            }
+            _ => Some(
+                CtCtx::read_checkpoint(&mut lines)
+                    .map_err(|e| format!("in the statistics of '{}': {}", name.0, e))?,
+            ),
+        };
+
+        benches.push(SavedBench {
+            seed,
+            batches,
+            elapsed,
+            stop_reason,
+            crop,
+            ctx,
+        });
+    }
+
+    Ok(SavedRun {
+        master_seed,
+        csv_len,
+        correction,
+        dist_tests,
+        benches,
+    })
+}
+
+/// Returns the token that represents the given distribution tests in a checkpoint, e.g., `ks,ad`,
+/// or `-` if there are none
+pub(crate) fn dist_tests_token(tests: &[DistTest]) -> String {
+    if tests.is_empty() {
+        return "-".to_string();
+    }
+    let tests: Vec<String> = tests.iter().map(|t| t.to_string()).collect();
+    tests.join(",")
+}
+
+/// Returns the token that represents the given stop reason in a checkpoint
+fn stop_reason_token(reason: StopReason) -> &'static str {
+    match reason {
+        StopReason::SingleRun => "single-run",
+        StopReason::MaxTime => "max-time",
+        StopReason::MaxSamples => "max-samples",
+        StopReason::MaxIterations => "max-iterations",
+        StopReason::LeakDetected => "leak-detected",
+        StopReason::EnoughSamples => "enough-samples",
+        StopReason::Interrupted => "interrupted",
+    }
+}
+
+/// Parses a token written by [`stop_reason_token`]
+fn parse_stop_reason(token: &str) -> Result<StopReason, String> {
+    let reason = match token {
+        "single-run" => StopReason::SingleRun,
+        "max-time" => StopReason::MaxTime,
+        "max-samples" => StopReason::MaxSamples,
+        "max-iterations" => StopReason::MaxIterations,
+        "leak-detected" => StopReason::LeakDetected,
+        "enough-samples" => StopReason::EnoughSamples,
+        "interrupted" => StopReason::Interrupted,
+        t => return Err(format!("unknown stop reason '{}'", t)),
+    };
+    Ok(reason)
+}
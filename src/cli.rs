@@ -0,0 +1,22 @@
+//! Parsing and validation of command-line arguments shared by the `main` that
+//! [`ctbench_main!`](crate::ctbench_main) generates and the `dudect-analyze` binary. The validators
+//! have the signature that `clap` expects of them.
+
+/// Parses a seed given on the command line as a hex number, with or without a `0x` prefix
+pub fn parse_seed(s: &str) -> Result<u64, String> {
+    u64::from_str_radix(s.trim_start_matches("0x"), 16).map_err(|e| e.to_string())
+}
+
+/// Checks that a seed given on the command line is a hex number
+pub fn validate_seed(s: String) -> Result<(), String> {
+    parse_seed(&s).map(|_| ())
+}
+
+/// Checks that a baseline name can be used as a directory name
+pub fn validate_baseline_name(name: String) -> Result<(), String> {
+    if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\\']) {
+        Err(format!("'{}' is not a valid baseline name", name))
+    } else {
+        Ok(())
+    }
+}
@@ -0,0 +1,171 @@
+//! The CSV format that raw samples are exported in. The first line is a comment identifying the
+//! crate version and the timer, e.g.,
+//!
+//! ```text
+//! # dudect-bencher 0.7.0, timer = cycles
+//! ```
+//!
+//! It is followed by a header line, and then one line per sample:
+//!
+//! ```text
+//! benchname,class,batch,seed,unit,measurement
+//! vec_eq,left,0,0x000000006b6c816d,cycles,152
+//! ```
+//!
+//! `class` is `left` or `right`, `batch` is the 0-based index of the call to the bench function
+//! that produced the sample, and `seed` is the seed of the bench's RNG.
+
+use crate::{ctbench::Class, timer::Timer};
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+/// The column header line of the CSV format
+pub(crate) const CSV_HEADER: &str = "benchname,class,batch,seed,unit,measurement";
+
+/// Writes raw samples to a CSV file
+pub(crate) struct CsvWriter {
+    out: BufWriter<File>,
+    unit: &'static str,
+}
+
+impl CsvWriter {
+    /// Creates (or truncates) the file at `path` and writes the header to it
+    pub(crate) fn create(path: &Path, timer: Timer) -> io::Result<CsvWriter> {
+        CsvWriter::open(path, timer, false, None)
+    }
+
+    /// Opens the file at `path` for appending, creating it if it doesn't exist, and writes the
+    /// header to it. The header lines are skipped when the file is read back, so a resumed run can
+    /// add its samples to those of the run it resumes. If `len` is set, the file is first truncated
+    /// to that many bytes, dropping whatever was written after the checkpoint being resumed.
+    pub(crate) fn append(path: &Path, timer: Timer, len: Option<u64>) -> io::Result<CsvWriter> {
+        CsvWriter::open(path, timer, true, len)
+    }
+
+    fn open(
+        path: &Path,
+        timer: Timer,
+        append: bool,
+        truncate_to: Option<u64>,
+    ) -> io::Result<CsvWriter> {
+        let file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .append(append)
+            .truncate(!append)
+            .open(path)
+            .map_err(|e| {
+                io::Error::new(
+                    e.kind(),
+                    format!("could not open '{}' for writing: {}", path.display(), e),
+                )
+            })?;
+        if let Some(len) = truncate_to {
+            if file.metadata()?.len() < len {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "'{}' is shorter than when the checkpoint was written",
+                        path.display()
+                    ),
+                ));
+            }
+            file.set_len(len)?;
+        }
+        let mut out = BufWriter::new(file);
+
+        let timer = timer.resolve();
+        writeln!(
+            out,
+            "# dudect-bencher {}, timer = {}",
+            env!("CARGO_PKG_VERSION"),
+            timer
+        )?;
+        writeln!(out, "{}", CSV_HEADER)?;
+
+        Ok(CsvWriter {
+            out,
+            unit: timer.unit(),
+        })
+    }
+
+    /// Writes every sample of a batch and flushes them to the file
+    pub(crate) fn write_batch(
+        &mut self,
+        name: &str,
+        seed: u64,
+        batch: usize,
+        (left, right): &(Vec<u64>, Vec<u64>),
+    ) -> io::Result<()> {
+        let classes = [("left", left), ("right", right)];
+        for (class, samples) in classes {
+            for x in samples {
+                writeln!(
+                    self.out,
+                    "{},{},{},0x{:016x},{},{}",
+                    name, class, batch, seed, self.unit, x
+                )?;
+            }
+        }
+
+        self.flush()
+    }
+
+    /// Flushes any buffered samples to the file
+    pub(crate) fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+
+    /// Flushes any buffered samples, and returns the length of the file in bytes
+    pub(crate) fn len(&mut self) -> io::Result<u64> {
+        self.flush()?;
+        Ok(self.out.get_ref().metadata()?.len())
+    }
+}
+
+/// A single sample read back from a CSV file
+pub(crate) struct CsvRecord<'a> {
+    pub(crate) name: &'a str,
+    pub(crate) class: Class,
+    pub(crate) batch: usize,
+    pub(crate) seed: u64,
+    pub(crate) measurement: u64,
+}
+
+/// Parses a line of a CSV file. Returns `Ok(None)` for comment and header lines.
+pub(crate) fn parse_line(line: &str) -> Result<Option<CsvRecord<'_>>, String> {
+    let line = line.trim_end();
+    if line.is_empty() || line.starts_with('#') || line == CSV_HEADER {
+        return Ok(None);
+    }
+
+    let fields: Vec<&str> = line.split(',').collect();
+    if fields.len() != 6 {
+        return Err(format!("expected 6 fields, found {}", fields.len()));
+    }
+    let class = match fields[1] {
+        "left" => Class::Left,
+        "right" => Class::Right,
+        c => return Err(format!("unknown class '{}'", c)),
+    };
+    let batch = fields[2]
+        .parse()
+        .map_err(|e| format!("invalid batch '{}': {}", fields[2], e))?;
+    let seed = u64::from_str_radix(fields[3].trim_start_matches("0x"), 16)
+        .map_err(|e| format!("invalid seed '{}': {}", fields[3], e))?;
+    let measurement = fields[5]
+        .parse()
+        .map_err(|e| format!("invalid measurement '{}': {}", fields[5], e))?;
+
+    Ok(Some(CsvRecord {
+        name: fields[0],
+        class,
+        batch,
+        seed,
+        measurement,
+    }))
+}
@@ -1,16 +1,27 @@
-use crate::stats;
+pub use crate::baseline::BaselineComparison;
+pub use crate::stats::{
+    BootstrapConfig, BootstrapResult, Correction, CropConfig, CropScheme, CtCtx, CtSummary,
+    CtTestResult, DistTest, DistTestResult,
+};
+use crate::{
+    baseline::{self, Baseline},
+    checkpoint::{self, BenchState, SavedBench, SavedRun},
+    csv::CsvWriter,
+    report::{DashboardReporter, OutputFormat, Reporter},
+    stats,
+    timer::{Clock, Timer},
+};
 
 use std::{
-    fs::{File, OpenOptions},
-    hint::black_box,
-    io::{self, Write},
+    collections::HashMap,
+    fmt, io,
     path::PathBuf,
     process,
     sync::{
-        atomic::{self, AtomicBool},
-        Arc,
+        atomic::{self, AtomicBool, AtomicUsize},
+        Arc, Once,
     },
-    time::Instant,
+    time::{Duration, Instant},
 };
 
 use ctrlc;
@@ -18,11 +29,11 @@ use rand::{Rng, SeedableRng};
 use rand_chacha::ChaChaRng;
 
 /// Just a static str representing the name of a function
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct BenchName(pub &'static str);
 
 impl BenchName {
-    fn padded(&self, column_count: usize) -> String {
+    pub(crate) fn padded(&self, column_count: usize) -> String {
         let mut name = self.0.to_string();
         let pad_len = column_count.saturating_sub(name.len());
         let pad = " ".repeat(pad_len);
@@ -33,60 +44,320 @@ impl BenchName {
 }
 
 /// A random number generator implementing [`rand::SeedableRng`]. This is given to every
-/// benchmarking function to use as a source of randomness.
+/// benchmarking function to use as a source of randomness. Every batch gets an RNG of its own,
+/// given by [`batch_rng`].
 pub type BenchRng = ChaChaRng;
 
+/// Returns the RNG that the bench `name` is given in batch `batch` (counting from 0), when the
+/// bench is seeded with `seed`. The RNG of every batch is derived from these alone, so any batch of
+/// a run can be regenerated in isolation from the seed printed for its bench, e.g., to reproduce
+/// the inputs of a batch that stood out, without replaying the batches before it.
+///
+/// ```
+/// use dudect_bencher::{ctbench::batch_rng, rand::RngExt};
+///
+/// let x: u64 = batch_rng(0xdeadbeef, "vec_eq", 5000).random();
+/// assert_eq!(x, batch_rng(0xdeadbeef, "vec_eq", 5000).random());
+/// assert_ne!(x, batch_rng(0xdeadbeef, "vec_eq", 5001).random());
+/// assert_ne!(x, batch_rng(0xdeadbeef, "arith", 5000).random());
+/// ```
+pub fn batch_rng(seed: u64, name: &str, batch: usize) -> BenchRng {
+    keyed_batch_rng(bench_key(seed, name), batch)
+}
+
+/// Returns the key of the RNGs of the bench `name` when it is seeded with `seed`. The key is drawn
+/// from the stream of the seed's RNG selected by a hash of the name, so that benches sharing a seed
+/// get independent RNGs.
+fn bench_key(seed: u64, name: &str) -> <BenchRng as SeedableRng>::Seed {
+    // 64-bit FNV-1a, which is stable across platforms and releases
+    let hash = name.bytes().fold(0xcbf29ce484222325u64, |h, b| {
+        (h ^ u64::from(b)).wrapping_mul(0x100000001b3)
+    });
+    let mut rng = BenchRng::seed_from_u64(seed);
+    rng.set_stream(hash);
+
+    let mut key = <BenchRng as SeedableRng>::Seed::default();
+    rng.fill_bytes(&mut key);
+    key
+}
+
+/// Returns the RNG for the given batch of the bench with the given key. Each batch gets the stream
+/// of the key's RNG selected by its index.
+fn keyed_batch_rng(key: <BenchRng as SeedableRng>::Seed, batch: usize) -> BenchRng {
+    let mut rng = BenchRng::from_seed(key);
+    rng.set_stream(batch as u64);
+    rng
+}
+
 /// A function that is to be benchmarked. This crate only supports statically-defined functions.
 pub type BenchFn = fn(&mut CtRunner, &mut BenchRng);
 
-// TODO: Consider giving this a lifetime so we don't have to copy names and vecs into it
-#[derive(Clone)]
-enum BenchEvent {
-    ContStart,
-    Begin(Vec<BenchName>),
-    Wait(BenchName),
-    Result(MonitorMsg),
-    Seed(u64, BenchName),
+/// The t-value above which a bench is considered to leak, unless configured otherwise
+pub const DEFAULT_T_THRESHOLD: f64 = 5.0;
+
+/// How often a checkpoint is written, unless configured otherwise
+pub const DEFAULT_CHECKPOINT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The directory that baselines are saved in, unless configured otherwise
+pub const DEFAULT_BASELINE_DIR: &str = "target/dudect";
+
+/// Whether a bench is considered to leak
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Verdict {
+    /// Every t-value is within the threshold, or the corrected p-value is at least alpha
+    Pass,
+    /// Some t-value exceeds the threshold, or the corrected p-value is below alpha
+    Leak,
 }
 
-type MonitorMsg = (BenchName, stats::CtSummary);
+impl Verdict {
+    /// Returns the verdict for the given summary under the given t-value threshold. Both the
+    /// first-order and the second-order t-values are checked.
+    pub fn of(summary: &CtSummary, threshold: f64) -> Verdict {
+        let max_abs_t = summary
+            .second_order_t
+            .map_or(summary.max_t.abs(), |t| summary.max_t.abs().max(t.abs()));
+        if max_abs_t > threshold {
+            Verdict::Leak
+        } else {
+            Verdict::Pass
+        }
+    }
+
+    /// Returns the verdict for the given summary at significance level `alpha`. The bench leaks if
+    /// the p-value of any t-test, corrected for multiple comparisons, is below `alpha`.
+    pub fn of_p_value(summary: &CtSummary, alpha: f64) -> Verdict {
+        if summary.corrected_p_value < alpha {
+            Verdict::Leak
+        } else {
+            Verdict::Pass
+        }
+    }
+}
 
-/// CtBencher is the primary interface for benchmarking. All setup for function inputs should be
-/// doen within the closure supplied to the `iter` method.
+impl fmt::Display for Verdict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Verdict::Pass => f.write_str("PASS"),
+            Verdict::Leak => f.write_str("LEAK"),
+        }
+    }
+}
+
+/// The outcome of running a single benchmark
+#[derive(Clone, Debug)]
+pub struct BenchReport {
+    /// The name of the benchmark
+    pub name: BenchName,
+    /// The seed that the benchmark's [`BenchRng`]s were derived from
+    pub seed: u64,
+    /// The number of batches, i.e., calls to the bench function, that went into this report
+    pub batches: usize,
+    /// The time spent running the bench, including computing statistics. In continuous mode, this
+    /// only counts the bench's own batches.
+    pub elapsed: Duration,
+    /// The summary of the test with the largest t-value
+    pub summary: CtSummary,
+    /// The results of every t-test that was run, uncropped first, followed by each percentile
+    /// crop in increasing order
+    pub tests: Vec<CtTestResult>,
+    /// The t-value threshold the bench was judged against
+    pub threshold: f64,
+    /// The significance level the bench was judged at, if it was judged by its corrected p-value
+    /// rather than by `threshold`
+    pub alpha: Option<f64>,
+    /// Whether the bench leaks
+    pub verdict: Verdict,
+    /// Why the bench stopped running, or `None` if it hasn't stopped, as is the case for
+    /// intermediate results
+    pub stop_reason: Option<StopReason>,
+    /// Bootstrap confidence intervals for the effect size, if [`BenchOpts::bootstrap`] is set.
+    /// These are only computed for final results.
+    pub bootstrap: Option<BootstrapResult>,
+    /// The statistics the bench accumulated, for final results. These can be
+    /// [merged](CtCtx::merge) with the statistics of other runs of the same bench.
+    pub ctx: Option<Arc<CtCtx>>,
+    /// How the bench compares with its result in [`BenchOpts::baseline`], for final results of
+    /// benches that are in the baseline
+    pub baseline: Option<BaselineComparison>,
+}
+
+/// The condition that stopped a bench from running more batches
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StopReason {
+    /// The bench function was called once, as no budget or adaptive mode was set
+    SingleRun,
+    /// The bench ran for [`BenchOpts::max_time`]
+    MaxTime,
+    /// The bench collected [`BenchOpts::max_samples`] samples
+    MaxSamples,
+    /// The bench function was called [`BenchOpts::max_iterations`] times
+    MaxIterations,
+    /// In adaptive mode, a t-value crossed the threshold
+    LeakDetected,
+    /// In adaptive mode, enough samples were collected to detect a leak of size
+    /// [`BenchOpts::min_effect`], and none was found
+    EnoughSamples,
+    /// Ctrl-C was pressed
+    Interrupted,
+}
+
+impl fmt::Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match *self {
+            StopReason::SingleRun => "single run",
+            StopReason::MaxTime => "time budget reached",
+            StopReason::MaxSamples => "sample budget reached",
+            StopReason::MaxIterations => "iteration budget reached",
+            StopReason::LeakDetected => "leak detected",
+            StopReason::EnoughSamples => "enough samples to rule out a leak of the minimum effect",
+            StopReason::Interrupted => "interrupted",
+        };
+        f.write_str(s)
+    }
+}
+
+impl BenchReport {
+    /// Assembles the report of a bench whose statistics are in `ctx`. The bench is judged at
+    /// significance level `alpha` if it is set, and against `threshold` otherwise.
+    pub(crate) fn new(
+        name: BenchName,
+        seed: u64,
+        batches: usize,
+        threshold: f64,
+        alpha: Option<f64>,
+        summary: CtSummary,
+        ctx: &CtCtx,
+    ) -> BenchReport {
+        let verdict = match alpha {
+            Some(alpha) => Verdict::of_p_value(&summary, alpha),
+            None => Verdict::of(&summary, threshold),
+        };
+        BenchReport {
+            name,
+            seed,
+            batches,
+            elapsed: Duration::ZERO,
+            summary,
+            tests: ctx.test_results(),
+            threshold,
+            alpha,
+            verdict,
+            stop_reason: None,
+            bootstrap: None,
+            ctx: None,
+            baseline: None,
+        }
+    }
+
+    /// Makes this the final report of a bench whose statistics are in `ctx`. This attaches the
+    /// statistics, computes the bootstrap confidence intervals if `bootstrap` is set, and compares
+    /// the result with `baseline` if it is set.
+    pub(crate) fn finalize(
+        &mut self,
+        ctx: &CtCtx,
+        bootstrap: Option<&BootstrapConfig>,
+        baseline: Option<&Baseline>,
+    ) {
+        if let Some(config) = bootstrap {
+            self.bootstrap = ctx.bootstrap(
+                self.summary.max_t_index,
+                config.resamples,
+                config.confidence,
+                config.seed.unwrap_or(self.seed),
+            );
+        }
+        self.ctx = Some(Arc::new(ctx.clone()));
+        self.baseline = baseline.map(|b| b.compare(self));
+    }
+}
+
+/// CtBencher is the primary interface for benchmarking. It holds the samples and statistics that
+/// a single bench has accumulated, along with the key of the RNGs that are given to its function.
 struct CtBencher {
     samples: (Vec<u64>, Vec<u64>),
-    ctx: Option<stats::CtCtx>,
-    file_out: Option<File>,
-    rng: BenchRng,
+    ctx: Option<CtCtx>,
+    batches: usize,
+    elapsed: Duration,
+    seed: u64,
+    // The key that the RNG of every batch is derived from
+    key: <BenchRng as SeedableRng>::Seed,
+    crop: CropConfig,
+    dist_tests: Vec<DistTest>,
+    correction: Correction,
+    // Whether every sample is kept for the bootstrap
+    keep_samples: bool,
+    // Why the bench stopped, if it has
+    stop_reason: Option<StopReason>,
 }
 
 impl CtBencher {
-    /// Creates and returns a new empty `CtBencher` whose `BenchRng` is zero-seeded
-    pub fn new() -> CtBencher {
+    /// Creates and returns a new empty `CtBencher` for the bench `name`, whose `BenchRng`s are
+    /// derived from `seed`, and whose samples are cropped according to `crop`. The rest of the
+    /// statistics are configured by `opts`.
+    fn new(name: BenchName, seed: u64, crop: CropConfig, opts: &BenchOpts) -> CtBencher {
         CtBencher {
             samples: (Vec::new(), Vec::new()),
             ctx: None,
-            file_out: None,
-            rng: BenchRng::seed_from_u64(0u64),
+            batches: 0,
+            elapsed: Duration::ZERO,
+            seed,
+            key: bench_key(seed, name.0),
+            crop,
+            dist_tests: opts.dist_tests.clone(),
+            correction: opts.correction,
+            keep_samples: opts.bootstrap.is_some(),
+            stop_reason: None,
         }
     }
 
-    /// Runs the bench function and returns the CtSummary
-    fn go(&mut self, f: BenchFn) -> stats::CtSummary {
+    /// Restores a `CtBencher` from the state it was checkpointed in. An interrupted bench picks up
+    /// where it left off, and a bench that stopped for any other reason stays stopped.
+    fn resume(name: BenchName, saved: SavedBench, crop: CropConfig, opts: &BenchOpts) -> CtBencher {
+        let mut cb = CtBencher::new(name, saved.seed, crop, opts);
+        cb.batches = saved.batches;
+        cb.elapsed = saved.elapsed;
+        cb.ctx = saved.ctx;
+        cb.stop_reason = saved.stop_reason.filter(|&r| r != StopReason::Interrupted);
+        cb
+    }
+
+    /// Returns the state of this bencher to checkpoint
+    fn state(&self, name: BenchName) -> BenchState<'_> {
+        BenchState {
+            name,
+            seed: self.seed,
+            batches: self.batches,
+            elapsed: self.elapsed,
+            stop_reason: self.stop_reason,
+            crop: &self.crop,
+            ctx: self.ctx.as_ref(),
+        }
+    }
+
+    /// Runs the bench function, timing it with `clock`, and returns the CtSummary
+    fn go(&mut self, f: BenchFn, clock: &mut Clock) -> CtSummary {
         // This populates self.samples
-        let mut runner = CtRunner::default();
-        f(&mut runner, &mut self.rng);
+        let mut runner = CtRunner {
+            clock: ::std::mem::take(clock),
+            ..CtRunner::default()
+        };
+        let mut rng = keyed_batch_rng(self.key, self.batches);
+        f(&mut runner, &mut rng);
         self.samples = runner.runtimes;
+        *clock = runner.clock;
 
         // Replace the old CtCtx with an updated one
-        let old_self = ::std::mem::replace(self, CtBencher::new());
-        let (summ, new_ctx) = stats::update_ct_stats(old_self.ctx, &old_self.samples);
-
-        // Copy the old stuff back in
-        self.samples = old_self.samples;
-        self.file_out = old_self.file_out;
+        let (summ, new_ctx) = stats::update_ct_stats(
+            self.ctx.take(),
+            &self.crop,
+            &self.dist_tests,
+            self.correction,
+            self.keep_samples,
+            &self.samples,
+        );
         self.ctx = Some(new_ctx);
-        self.rng = old_self.rng;
+        self.batches += 1;
 
         summ
     }
@@ -95,213 +366,785 @@ impl CtBencher {
     fn rand_seed() -> u64 {
         rand::rng().next_u64()
     }
+}
 
-    /// Reseeds the internal RNG with the given seed
-    pub fn seed_with(&mut self, seed: u64) {
-        self.rng = BenchRng::seed_from_u64(seed);
-    }
-
-    /// Clears out all sample and contextual data
-    fn clear_data(&mut self) {
-        self.samples = (Vec::new(), Vec::new());
-        self.ctx = None;
-    }
+/// The state that is shared by every bench in a run
+struct RunEnv {
+    clock: Clock,
+    file_out: Option<CsvWriter>,
+    // Where to checkpoint the run, and how often
+    checkpoint: Option<(PathBuf, Duration)>,
+    last_checkpoint: Instant,
+    // The result of every bench in the baseline that results are compared with
+    baselines: HashMap<&'static str, Baseline>,
+    master_seed: u64,
+    // Marks the run as in progress until the state is dropped
+    run: ActiveRun,
 }
 
-/// Represents a single benchmark to conduct
+/// A bench that is part of a run, along with its t-value threshold and its state
+type BenchEntry = (BenchMetadata, f64, CtBencher);
+
+/// Represents a single benchmark to conduct. If `threshold` or `crop` is set, it takes precedence
+/// over [`BenchOpts::threshold`] or [`BenchOpts::crop`], respectively, for this bench.
 pub struct BenchMetadata {
     pub name: BenchName,
     pub seed: Option<u64>,
     pub benchfn: BenchFn,
+    pub threshold: Option<f64>,
+    pub crop: Option<CropConfig>,
 }
 
 /// Benchmarking options.
 ///
-/// When `continuous` is set, it will continuously set the first (alphabetically) of the benchmarks
-/// after they have been optionally filtered.
+/// When `continuous` is set, the benchmarks are run continuously in round-robin fashion: each
+/// round, every bench that is still running is run for one more batch, accumulating samples in its
+/// own statistics.
 ///
-/// When `filter` is set and `continuous` is not set, only benchmarks whose names contain the
-/// filter string as a substring will be executed.
+/// When `filter` is set, only benchmarks whose names contain the filter string as a substring will
+/// be executed. In continuous mode, a filter that matches no benchmark is an `InvalidInput` error.
 ///
 /// `file_out` is optionally the filename where CSV output of raw runtime data should be written
+///
+/// `timer` is the source of timestamps used to measure runtimes
+///
+/// `format` is the format that [`run_benches_console`] prints results in
+///
+/// `threshold` is the t-value above which a bench is considered to leak, for benches that don't
+/// set their own. If unset, [`DEFAULT_T_THRESHOLD`] is used.
+///
+/// `max_time`, `max_samples`, and `max_iterations` set a budget for every bench. Each bench's
+/// function is called repeatedly, accumulating samples, until any of the set limits is reached:
+/// the bench has run for `max_time`, collected `max_samples` samples in total, or called its
+/// function `max_iterations` times. If none are set, each bench's function is called once, or
+/// forever in continuous mode.
+///
+/// When `min_effect` is set, benches run in adaptive mode: each bench's function is called
+/// repeatedly until the verdict is settled. That is, until a t-value crosses the threshold, or
+/// until enough samples have been collected that a leak with an effect size (tau) of at least
+/// `min_effect` would have been detected. That is, until the test that produced the max t has
+/// `(threshold/min_effect)^2` samples, counting both classes together, and the effect it measures
+/// is below `min_effect`, i.e., its `(5/tau)^2` estimate is above `(5/min_effect)^2`. Any budget
+/// that is set still applies.
+///
+/// `crop` sets the percentiles that the runtime distributions are cropped at, for benches that
+/// don't set their own. By default, these are the 100 percentiles used by the reference dudect
+/// implementation.
+///
+/// `dist_tests` are the nonparametric tests that are run over every sample collected so far, in
+/// addition to the t-tests. Their results are reported, but don't affect the verdict. While any are
+/// set, every sample is kept in memory.
+///
+/// `correction` is how the p-values of the t-tests, one for every crop and one for the
+/// second-order test, are corrected for multiple comparisons. When `alpha` is set, benches are
+/// judged by their corrected p-value instead of by their t-values: a bench leaks if its corrected
+/// p-value is below `alpha`. Adaptive mode still uses the t-value threshold to decide how many
+/// samples are enough.
+///
+/// When `checkpoint` is set, the state of every bench is written to that file every
+/// `checkpoint_interval` (by default, [`DEFAULT_CHECKPOINT_INTERVAL`]), and once more when the run
+/// ends. When `resume` is set, the run picks up from the checkpoint in that file: benches that had
+/// finished are reported as they were, and the rest continue from their saved seed, statistics,
+/// batch count, and elapsed time, as well as the master seed. The checkpoint must have been written
+/// by the same version of this crate for the same list of benches. While resuming, checkpoints are
+/// written to the `resume` file unless `checkpoint` is set, and samples are appended to `file_out`
+/// rather than overwriting it.
+///
+/// When `save_baseline` is set, the final result of every bench is saved under that name in
+/// `baseline_dir` (by default, [`DEFAULT_BASELINE_DIR`]) once the run ends. When `baseline` is
+/// set, the final result of every bench is compared with its result in the baseline of that name,
+/// which must exist, in [`BenchReport::baseline`].
+///
+/// `seed` is the master seed of the run, which seeds every bench that isn't seeded otherwise, and
+/// is random if unset. When it is set, it seeds every bench, taking precedence over
+/// [`BenchMetadata::seed`]. `bench_seeds` seeds the bench with the given name, taking precedence
+/// over both. Every name in `bench_seeds` must be the name of a bench. The RNG of every batch of a
+/// bench is derived from the bench's seed, its name, and the batch's index, as given by
+/// [`batch_rng`]. A resumed run keeps the seeds in its checkpoint, so when `resume` is set, `seed`
+/// and `bench_seeds` must match them, or an `InvalidInput` error is returned.
 #[derive(Default)]
 pub struct BenchOpts {
     pub continuous: bool,
     pub filter: Option<String>,
     pub file_out: Option<PathBuf>,
+    pub timer: Timer,
+    pub format: OutputFormat,
+    pub threshold: Option<f64>,
+    pub max_time: Option<Duration>,
+    pub max_samples: Option<usize>,
+    pub max_iterations: Option<usize>,
+    pub min_effect: Option<f64>,
+    pub crop: CropConfig,
+    pub dist_tests: Vec<DistTest>,
+    pub correction: Correction,
+    pub alpha: Option<f64>,
+    pub bootstrap: Option<BootstrapConfig>,
+    pub checkpoint: Option<PathBuf>,
+    pub checkpoint_interval: Option<Duration>,
+    pub resume: Option<PathBuf>,
+    pub save_baseline: Option<String>,
+    pub baseline: Option<String>,
+    pub baseline_dir: Option<PathBuf>,
+    pub seed: Option<u64>,
+    pub bench_seeds: Vec<(String, u64)>,
 }
 
-struct ConsoleBenchState {
-    // Number of columns to fill when aligning names
-    max_name_len: usize,
-}
-
-impl ConsoleBenchState {
-    fn write_plain(&mut self, s: &str) -> io::Result<()> {
-        let mut stdout = io::stdout();
-        stdout.write_all(s.as_bytes())?;
-        stdout.flush()
+impl BenchOpts {
+    /// Returns the directory that baselines are saved in
+    pub(crate) fn baseline_dir(&self) -> PathBuf {
+        self.baseline_dir
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_BASELINE_DIR))
     }
 
-    fn write_bench_start(&mut self, name: &BenchName) -> io::Result<()> {
-        let name = name.padded(self.max_name_len);
-        self.write_plain(&format!("bench {} ... ", name))
+    /// Returns an `InvalidInput` error if any of the options are out of range
+    pub(crate) fn validate(&self) -> io::Result<()> {
+        let invalid = |e: String| io::Error::new(io::ErrorKind::InvalidInput, e);
+        self.crop.scheme.validate().map_err(invalid)?;
+        if let Some(ref config) = self.bootstrap {
+            config.validate().map_err(invalid)?;
+        }
+        Ok(())
     }
+}
 
-    fn write_seed(&mut self, seed: u64, name: &BenchName) -> io::Result<()> {
-        let name = name.padded(self.max_name_len);
-        self.write_plain(&format!("bench {} seeded with 0x{:016x}\n", name, seed))
-    }
+impl BenchOpts {
+    /// Returns why a bench that has gotten as far as `report` should stop, or `None` if it should
+    /// keep running
+    fn stop_reason(&self, report: &BenchReport) -> Option<StopReason> {
+        if let Some(min_effect) = self.min_effect {
+            // A leak of effect size tau is detected with t > threshold after about
+            // (threshold/tau)^2 samples, counting both classes together. The verdict is settled
+            // once the test that produced max t has that many samples for tau = min_effect, and
+            // its measured (5/tau)^2 is above (5/min_effect)^2, i.e., the effect it measured is
+            // smaller than min_effect. A larger effect that isn't significant yet, as can happen
+            // when judging by alpha, keeps the bench running.
+            let summary = &report.summary;
+            let needed = (report.threshold / min_effect).powi(2);
+            if report.verdict == Verdict::Leak {
+                return Some(StopReason::LeakDetected);
+            } else if summary.sample_size as f64 >= needed
+                && summary.needed_samples() > (5f64 / min_effect).powi(2)
+            {
+                return Some(StopReason::EnoughSamples);
+            }
+        } else if self.max_time.is_none()
+            && self.max_samples.is_none()
+            && self.max_iterations.is_none()
+        {
+            return if self.continuous {
+                None
+            } else {
+                Some(StopReason::SingleRun)
+            };
+        }
 
-    fn write_run_start(&mut self, len: usize) -> io::Result<()> {
-        let noun = if len != 1 { "benches" } else { "bench" };
-        self.write_plain(&format!("\nrunning {} {}\n", len, noun))
+        let (left, right) = report.summary.class_sizes;
+        if self.max_time.is_some_and(|t| report.elapsed >= t) {
+            Some(StopReason::MaxTime)
+        } else if self.max_samples.is_some_and(|n| left + right >= n) {
+            Some(StopReason::MaxSamples)
+        } else if self.max_iterations.is_some_and(|n| report.batches >= n) {
+            Some(StopReason::MaxIterations)
+        } else {
+            None
+        }
     }
+}
 
-    fn write_continuous_start(&mut self) -> io::Result<()> {
-        self.write_plain("running 1 benchmark continuously\n")
+/// Runs the given benches under the given options and prints the output to the console in the
+/// format given by `opts.format`. This is [`run_benches_with_reporter`] with the reporter given by
+/// [`OutputFormat::reporter`], except that pretty output in continuous mode is shown on a
+/// [`DashboardReporter`].
+pub fn run_benches_console(
+    opts: BenchOpts,
+    benches: Vec<BenchMetadata>,
+) -> io::Result<Vec<BenchReport>> {
+    // TODO: Consider making this run in its own thread
+    let mut reporter: Box<dyn Reporter> = if opts.continuous && opts.format == OutputFormat::Pretty
+    {
+        Box::new(DashboardReporter::new())
+    } else {
+        opts.format.reporter()
+    };
+    run_benches_with_reporter(&opts, benches, &mut *reporter)
+}
+
+/// The number of times Ctrl-C was pressed while a run was in progress. A run was interrupted if
+/// this changed since it started.
+static INTERRUPTS: AtomicUsize = AtomicUsize::new(0);
+
+/// The number of runs in progress. While there are any, Ctrl-C stops them rather than the process.
+static ACTIVE_RUNS: AtomicUsize = AtomicUsize::new(0);
+
+/// Set when Ctrl-C is pressed while runs are in progress, and cleared once they have all finished
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Marks a run as in progress for as long as it's alive. While any run is in progress, the first
+/// Ctrl-C interrupts every run in progress, and the second one exits the process. Outside of a
+/// run, Ctrl-C exits the process as usual. Runs that start after a Ctrl-C aren't affected by it.
+struct ActiveRun {
+    // The value of INTERRUPTS when the run started
+    interrupts: usize,
+}
+
+impl ActiveRun {
+    /// Installs the Ctrl-C handler, if it isn't already installed, and starts a run
+    fn start() -> ActiveRun {
+        static HANDLER: Once = Once::new();
+        HANDLER.call_once(|| {
+            // This fails if the program embedding us has its own handler. In that case Ctrl-C is
+            // left to that handler, and runs can't be interrupted gracefully.
+            let _ = ctrlc::set_handler(|| {
+                if ACTIVE_RUNS.load(atomic::Ordering::SeqCst) == 0
+                    || INTERRUPTED.swap(true, atomic::Ordering::SeqCst)
+                {
+                    process::exit(130);
+                }
+                INTERRUPTS.fetch_add(1, atomic::Ordering::SeqCst);
+            });
+        });
+
+        ACTIVE_RUNS.fetch_add(1, atomic::Ordering::SeqCst);
+        ActiveRun {
+            interrupts: INTERRUPTS.load(atomic::Ordering::SeqCst),
+        }
     }
 
-    fn write_result(&mut self, summ: &stats::CtSummary) -> io::Result<()> {
-        self.write_plain(&format!(": {}\n", summ.fmt()))
+    /// Returns whether Ctrl-C was pressed since the run started
+    fn killed(&self) -> bool {
+        INTERRUPTS.load(atomic::Ordering::SeqCst) != self.interrupts
     }
+}
 
-    fn write_run_finish(&mut self) -> io::Result<()> {
-        self.write_plain("\ndudect benches complete\n\n")
+impl Drop for ActiveRun {
+    fn drop(&mut self) {
+        // Once the last run has finished, the next Ctrl-C interrupts the runs after it again
+        if ACTIVE_RUNS.fetch_sub(1, atomic::Ordering::SeqCst) == 1 {
+            INTERRUPTED.store(false, atomic::Ordering::SeqCst);
+        }
     }
 }
 
-/// Runs the given benches under the given options and prints the output to the console
-pub fn run_benches_console(opts: BenchOpts, benches: Vec<BenchMetadata>) -> io::Result<()> {
-    // TODO: Consider making this do screen updates in continuous mode
-    // TODO: Consider making this run in its own thread
-    fn callback(event: &BenchEvent, st: &mut ConsoleBenchState) -> io::Result<()> {
-        match (*event).clone() {
-            BenchEvent::ContStart => st.write_continuous_start(),
-            BenchEvent::Begin(ref filtered_benches) => st.write_run_start(filtered_benches.len()),
-            BenchEvent::Wait(ref b) => st.write_bench_start(b),
-            BenchEvent::Result(msg) => {
-                let (_, summ) = msg;
-                st.write_result(&summ)
-            }
-            BenchEvent::Seed(seed, ref name) => st.write_seed(seed, name),
+/// Runs the given benches under the given options and returns a report for each, in the order they
+/// were run. Nothing is printed.
+///
+/// In continuous mode without a budget, this runs until Ctrl-C is pressed. When Ctrl-C is pressed,
+/// the current batch is finished and no further batches or benches are run. The interrupted bench
+/// is reported with [`StopReason::Interrupted`], and this returns normally with the reports of
+/// every bench that ran. Pressing Ctrl-C a second time exits the process immediately. Several runs
+/// can be in progress at once, e.g., on different threads, and Ctrl-C interrupts all of them.
+///
+/// ```
+/// use dudect_bencher::{
+///     ctbench::{BenchMetadata, BenchName, BenchOpts},
+///     run_benches, BenchRng, Class, CtRunner,
+/// };
+///
+/// fn foo(runner: &mut CtRunner, _rng: &mut BenchRng) {
+///     for _ in 0..1000 {
+///         runner.run_one(Class::Left, || 0);
+///         runner.run_one(Class::Right, || 0);
+///     }
+/// }
+///
+/// let benches = vec![BenchMetadata {
+///     name: BenchName("foo"),
+///     seed: Some(0xdeadbeef),
+///     benchfn: foo,
+///     threshold: None,
+///     crop: None,
+/// }];
+/// let reports = run_benches(&BenchOpts::default(), benches).unwrap();
+///
+/// assert_eq!(reports[0].name, BenchName("foo"));
+/// assert_eq!(reports[0].seed, 0xdeadbeef);
+/// assert_eq!(reports[0].tests[0].sample_sizes, (1000, 1000));
+/// ```
+pub fn run_benches(opts: &BenchOpts, benches: Vec<BenchMetadata>) -> io::Result<Vec<BenchReport>> {
+    run_benches_with_reporter(opts, benches, &mut ())
+}
+
+/// Runs the given benches under the given options, sending every event of the run to `reporter`,
+/// and returns a report for each bench, in the order they were run
+pub fn run_benches_with_reporter(
+    opts: &BenchOpts,
+    benches: Vec<BenchMetadata>,
+    reporter: &mut dyn Reporter,
+) -> io::Result<Vec<BenchReport>> {
+    opts.validate()?;
+    for bench in benches.iter() {
+        if let Some(ref crop) = bench.crop {
+            crop.scheme.validate().map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("invalid crop for '{}': {}", bench.name.0, e),
+                )
+            })?;
         }
     }
+    if let Some((name, _)) = opts
+        .bench_seeds
+        .iter()
+        .find(|(name, _)| !benches.iter().any(|b| b.name.0 == name))
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("cannot seed '{}': there is no bench with that name", name),
+        ));
+    }
 
-    let mut st = ConsoleBenchState {
-        max_name_len: benches.iter().map(|t| t.name.0.len()).max().unwrap_or(0),
+    let filter = &opts.filter;
+    let filtered_benches = filter_benches(filter, benches);
+    if opts.continuous && filtered_benches.is_empty() {
+        if let Some(ref f) = *filter {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("no benchmark matching '{}' was found", f),
+            ));
+        }
+    }
+
+    // Set up the timer and read the checkpoint first, so that we fail before touching the output
+    // file if either is unavailable
+    let clock = Clock::new(opts.timer)?;
+    let filtered_names: Vec<BenchName> = filtered_benches.iter().map(|b| b.name).collect();
+    let saved = opts
+        .resume
+        .as_ref()
+        .map(|path| checkpoint::read(path, &filtered_names))
+        .transpose()?;
+    if let Some(ref saved) = saved {
+        check_resumed_seeds(opts, &filtered_benches, saved)?;
+        check_resumed_options(opts, &filtered_benches, saved)?;
+    }
+    let (master_seed, csv_len, saved) = match saved {
+        Some(s) => (s.master_seed, s.csv_len, Some(s.benches)),
+        None => (opts.seed.unwrap_or_else(CtBencher::rand_seed), None, None),
+    };
+    let baselines = match opts.baseline {
+        Some(ref name) => baseline::load(&opts.baseline_dir(), name, &filtered_names)?,
+        None => HashMap::new(),
+    };
+
+    // Write the CSV header line to the file if the file is defined. A resumed run adds to the
+    // samples of the run it resumes, as they were when the checkpoint was written.
+    let file_out = opts
+        .file_out
+        .as_ref()
+        .map(|filename| match saved {
+            Some(_) => CsvWriter::append(filename, opts.timer, csv_len),
+            None => CsvWriter::create(filename, opts.timer),
+        })
+        .transpose()?;
+    let checkpoint = opts
+        .checkpoint
+        .as_ref()
+        .or(opts.resume.as_ref())
+        .map(|path| {
+            let interval = opts
+                .checkpoint_interval
+                .unwrap_or(DEFAULT_CHECKPOINT_INTERVAL);
+            (path.clone(), interval)
+        });
+
+    // Every bench gets its own statistics and RNGs, which persist across batches
+    let bencher_list = new_benchers(opts, master_seed, filtered_benches, saved);
+
+    reporter.run_start(&filtered_names, opts.continuous)?;
+    reporter.master_seed(master_seed)?;
+
+    // Catch Ctrl-C for as long as we're running, so that we can stop cleanly
+    let mut env = RunEnv {
+        clock,
+        file_out,
+        checkpoint,
+        last_checkpoint: Instant::now(),
+        baselines,
+        master_seed,
+        run: ActiveRun::start(),
+    };
+    let res = if opts.continuous {
+        run_round_robin(opts, bencher_list, &mut env, reporter)
+    } else {
+        run_sequential(opts, bencher_list, &mut env, reporter)
     };
+    let mut file_out = env.file_out.take();
+    drop(env);
+    let reports = res?;
+
+    // Make sure every sample is on disk before we report that we're done
+    if let Some(f) = file_out.as_mut() {
+        f.flush()?;
+    }
+    if let Some(ref name) = opts.save_baseline {
+        baseline::save(&opts.baseline_dir(), name, &reports)?;
+    }
+    reporter.run_finish(&reports)?;
+    Ok(reports)
+}
 
-    run_benches(&opts, benches, |x| callback(&x, &mut st))?;
-    st.write_run_finish()
+/// Creates the state of each of the given benches, restoring it from `saved` if we're resuming
+fn new_benchers(
+    opts: &BenchOpts,
+    master_seed: u64,
+    benches: Vec<BenchMetadata>,
+    saved: Option<Vec<SavedBench>>,
+) -> Vec<BenchEntry> {
+    let mut saved = saved.map(|s| s.into_iter());
+    benches
+        .into_iter()
+        .map(|bench| {
+            let threshold = bench_threshold(opts, &bench);
+            let crop = bench_crop(opts, &bench);
+            let cb = match saved.as_mut().and_then(|s| s.next()) {
+                Some(s) => CtBencher::resume(bench.name, s, crop, opts),
+                None => {
+                    let seed = bench_seed(opts, &bench, master_seed);
+                    CtBencher::new(bench.name, seed, crop, opts)
+                }
+            };
+            (bench, threshold, cb)
+        })
+        .collect()
 }
 
-/// Returns an atomic bool that indicates whether Ctrl-C was pressed
-fn setup_kill_bit() -> Arc<AtomicBool> {
-    let x = Arc::new(AtomicBool::new(false));
-    let y = x.clone();
+/// Runs each of the given benches to completion in turn, stopping early if Ctrl-C is pressed
+fn run_sequential(
+    opts: &BenchOpts,
+    mut bencher_list: Vec<BenchEntry>,
+    env: &mut RunEnv,
+    reporter: &mut dyn Reporter,
+) -> io::Result<Vec<BenchReport>> {
+    let mut reports = Vec::new();
+    for i in 0..bencher_list.len() {
+        // If we were interrupted, don't start any more benches
+        if env.run.killed() {
+            break;
+        }
 
-    ctrlc::set_handler(move || y.store(true, atomic::Ordering::SeqCst))
-        .expect("Error setting Ctrl-C handler");
+        let (bench, threshold, cb) = &bencher_list[i];
+        reporter.seed(bench.name, cb.seed)?;
+        reporter.bench_start(bench.name)?;
+        let report = if cb.stop_reason.is_some() {
+            // The bench finished in the run we resumed from
+            stopped_report(opts, bench, *threshold, cb, env)
+        } else {
+            loop {
+                let (bench, threshold, cb) = &mut bencher_list[i];
+                let report = run_batch(opts, bench, *threshold, cb, env)?;
+                save_checkpoint(opts, env, &bencher_list, false)?;
+                if report.stop_reason.is_some() {
+                    break report;
+                }
+                reporter.intermediate_result(&report)?;
+            }
+        };
+        reporter.bench_finish(&report)?;
+        reports.push(report);
+    }
 
-    x
+    save_checkpoint(opts, env, &bencher_list, true)?;
+    Ok(reports)
 }
 
-fn run_benches<F>(opts: &BenchOpts, benches: Vec<BenchMetadata>, mut callback: F) -> io::Result<()>
-where
-    F: FnMut(BenchEvent) -> io::Result<()>,
-{
-    let filter = &opts.filter;
-    let filtered_benches = filter_benches(filter, benches);
-    let filtered_names = filtered_benches.iter().map(|b| b.name).collect();
-
-    // Write the CSV header line to the file if the file is defined
-    let mut file_out = opts.file_out.as_ref().map(|filename| {
-        OpenOptions::new()
-            .write(true)
-            .truncate(true)
-            .create(true)
-            .open(filename)
-            .unwrap_or_else(|e| panic!("Could not open file '{:?}' for writing: {e}", filename))
-    });
-    file_out.as_mut().map(|f| {
-        f.write(b"benchname,class,runtime")
-            .expect("Error writing CSV header to file")
-    });
+/// Runs a batch of each of the given benches per round, until every bench has stopped or Ctrl-C is
+/// pressed
+fn run_round_robin(
+    opts: &BenchOpts,
+    mut bencher_list: Vec<BenchEntry>,
+    env: &mut RunEnv,
+    reporter: &mut dyn Reporter,
+) -> io::Result<Vec<BenchReport>> {
+    // The latest report of every bench that has run at least one batch. Benches that finished in
+    // the run we resumed from are reported right away.
+    let mut latest: Vec<Option<BenchReport>> = Vec::new();
+    for (bench, _, cb) in bencher_list.iter() {
+        reporter.seed(bench.name, cb.seed)?;
+    }
+    for (bench, threshold, cb) in bencher_list.iter() {
+        let report = if cb.stop_reason.is_some() {
+            let r = stopped_report(opts, bench, *threshold, cb, env);
+            reporter.bench_start(bench.name)?;
+            reporter.bench_finish(&r)?;
+            Some(r)
+        } else {
+            None
+        };
+        latest.push(report);
+    }
 
-    // Make a bencher with the optional file output specified
-    let mut cb: CtBencher = {
-        let mut d = CtBencher::new();
-        d.file_out = file_out;
-        d
-    };
+    loop {
+        let mut ran_any = false;
+        for ((bench, threshold, cb), report) in bencher_list.iter_mut().zip(latest.iter_mut()) {
+            if report.as_ref().is_some_and(|r| r.stop_reason.is_some()) {
+                continue;
+            }
 
-    if opts.continuous {
-        callback(BenchEvent::ContStart)?;
+            // If we were interrupted, finish off every bench that has started, and don't start any
+            // new ones
+            if env.run.killed() {
+                if let Some(r) = report.as_mut() {
+                    r.stop_reason = Some(StopReason::Interrupted);
+                    cb.stop_reason = r.stop_reason;
+                    if let Some(ctx) = cb.ctx.as_ref() {
+                        let baseline = env.baselines.get(bench.name.0);
+                        r.finalize(ctx, opts.bootstrap.as_ref(), baseline);
+                    }
+                    reporter.bench_finish(r)?;
+                }
+                continue;
+            }
 
-        if filtered_benches.is_empty() {
-            match *filter {
-                Some(ref f) => panic!("No benchmark matching '{}' was found", f),
-                None => return Ok(()),
+            if report.is_none() {
+                reporter.bench_start(bench.name)?;
+            }
+            let r = run_batch(opts, bench, *threshold, cb, env)?;
+            if r.stop_reason.is_some() {
+                reporter.bench_finish(&r)?;
+            } else {
+                reporter.intermediate_result(&r)?;
             }
+            *report = Some(r);
+            ran_any = true;
         }
 
-        // Get a bit that tells us when we've been killed
-        let kill_bit = setup_kill_bit();
+        if !ran_any {
+            break;
+        }
+        save_checkpoint(opts, env, &bencher_list, false)?;
+        let round: Vec<BenchReport> = latest.iter().flatten().cloned().collect();
+        reporter.round_finish(&round)?;
+    }
+
+    save_checkpoint(opts, env, &bencher_list, true)?;
+    Ok(latest.into_iter().flatten().collect())
+}
 
-        // Continuously run the first matched bench we see
-        let mut filtered_benches = filtered_benches;
-        let bench = filtered_benches.remove(0);
+/// Writes the state of every bench to the checkpoint file, if one is set. Unless `force` is set,
+/// this only happens once the checkpoint interval has passed since the last checkpoint.
+fn save_checkpoint(
+    opts: &BenchOpts,
+    env: &mut RunEnv,
+    bencher_list: &[BenchEntry],
+    force: bool,
+) -> io::Result<()> {
+    let Some((path, interval)) = env.checkpoint.as_ref() else {
+        return Ok(());
+    };
+    if !force && env.last_checkpoint.elapsed() < *interval {
+        return Ok(());
+    }
 
-        // If a seed was specified for this bench, use it. Otherwise, use a random seed
-        let seed = bench.seed.unwrap_or_else(CtBencher::rand_seed);
-        cb.seed_with(seed);
-        callback(BenchEvent::Seed(seed, bench.name))?;
+    let states: Vec<BenchState> = bencher_list
+        .iter()
+        .map(|(bench, _, cb)| cb.state(bench.name))
+        .collect();
+    let csv_len = env.file_out.as_mut().map(|f| f.len()).transpose()?;
+    checkpoint::write(
+        path,
+        env.master_seed,
+        csv_len,
+        opts.correction,
+        &opts.dist_tests,
+        &states,
+    )?;
+    env.last_checkpoint = Instant::now();
+    Ok(())
+}
 
-        loop {
-            callback(BenchEvent::Wait(bench.name))?;
-            let msg = run_bench_with_bencher(&bench.name, bench.benchfn, &mut cb);
-            callback(BenchEvent::Result(msg))?;
+/// Returns an `InvalidInput` error if the seeds in the options differ from those of the run being
+/// resumed from `saved`. A resumed run keeps its seeds, so that it stays reproducible.
+fn check_resumed_seeds(
+    opts: &BenchOpts,
+    benches: &[BenchMetadata],
+    saved: &SavedRun,
+) -> io::Result<()> {
+    let conflict = |what: String, given: u64, saved: u64| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "cannot resume with {} 0x{:016x}: the checkpoint has 0x{:016x}",
+                what, given, saved
+            ),
+        )
+    };
 
-            // Check if the program has been killed. If so, exit
-            if kill_bit.load(atomic::Ordering::SeqCst) {
-                process::exit(0);
-            }
+    if let Some(seed) = opts.seed.filter(|&s| s != saved.master_seed) {
+        return Err(conflict("master seed".to_string(), seed, saved.master_seed));
+    }
+    for (bench, s) in benches.iter().zip(saved.benches.iter()) {
+        let overridden =
+            opts.seed.is_some() || opts.bench_seeds.iter().any(|(n, _)| n == bench.name.0);
+        let seed = bench_seed(opts, bench, saved.master_seed);
+        if overridden && seed != s.seed {
+            return Err(conflict(
+                format!("seed of '{}'", bench.name.0),
+                seed,
+                s.seed,
+            ));
         }
-    } else {
-        callback(BenchEvent::Begin(filtered_names))?;
-
-        // Run different benches
-        for bench in filtered_benches {
-            // Clear the data out from the previous bench, but keep the CSV file open
-            cb.clear_data();
+    }
+    Ok(())
+}
 
-            // If a seed was specified for this bench, use it. Otherwise, use a random seed
-            let seed = bench.seed.unwrap_or_else(CtBencher::rand_seed);
-            cb.seed_with(seed);
-            callback(BenchEvent::Seed(seed, bench.name))?;
+/// Returns an `InvalidInput` error if the correction, distribution tests, or cropping of the benches
+/// in the options differ from those of the run being resumed from `saved`. The statistics of a
+/// resumed run were accumulated under the options it was started with, and can't be changed.
+fn check_resumed_options(
+    opts: &BenchOpts,
+    benches: &[BenchMetadata],
+    saved: &SavedRun,
+) -> io::Result<()> {
+    let conflict = |what: String, given: String, saved: String| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "cannot resume {} '{}': the checkpoint has '{}'",
+                what, given, saved
+            ),
+        )
+    };
 
-            callback(BenchEvent::Wait(bench.name))?;
-            let msg = run_bench_with_bencher(&bench.name, bench.benchfn, &mut cb);
-            callback(BenchEvent::Result(msg))?;
+    if opts.correction != saved.correction {
+        return Err(conflict(
+            "with correction".to_string(),
+            opts.correction.to_string(),
+            saved.correction.to_string(),
+        ));
+    }
+    if opts.dist_tests != saved.dist_tests {
+        return Err(conflict(
+            "with distribution tests".to_string(),
+            checkpoint::dist_tests_token(&opts.dist_tests),
+            checkpoint::dist_tests_token(&saved.dist_tests),
+        ));
+    }
+    for (bench, s) in benches.iter().zip(saved.benches.iter()) {
+        let crop = bench_crop(opts, bench);
+        let reservoir =
+            |c: &CropConfig| c.reservoir_size.map_or("-".to_string(), |r| r.to_string());
+        if crop.scheme != s.crop.scheme {
+            return Err(conflict(
+                format!("'{}' with crop scheme", bench.name.0),
+                crop.scheme.to_string(),
+                s.crop.scheme.to_string(),
+            ));
+        }
+        if crop.two_sided != s.crop.two_sided {
+            return Err(conflict(
+                format!("'{}' with two-sided crops", bench.name.0),
+                crop.two_sided.to_string(),
+                s.crop.two_sided.to_string(),
+            ));
+        }
+        if crop.reservoir_size != s.crop.reservoir_size {
+            return Err(conflict(
+                format!("'{}' with crop reservoir", bench.name.0),
+                reservoir(&crop),
+                reservoir(&s.crop),
+            ));
         }
-        Ok(())
     }
+    Ok(())
+}
+
+/// Returns the seed for the given bench's RNGs. A seed given for this bench in the options takes
+/// precedence over one given for every bench, which takes precedence over the bench's own. If no
+/// seed was specified, the master seed is used.
+fn bench_seed(opts: &BenchOpts, bench: &BenchMetadata, master_seed: u64) -> u64 {
+    opts.bench_seeds
+        .iter()
+        .rev()
+        .find(|(name, _)| name == bench.name.0)
+        .map(|&(_, seed)| seed)
+        .or(opts.seed)
+        .or(bench.seed)
+        .unwrap_or(master_seed)
+}
+
+/// Returns the t-value threshold for the given bench. The bench's own threshold takes precedence
+/// over the one in the options.
+fn bench_threshold(opts: &BenchOpts, bench: &BenchMetadata) -> f64 {
+    bench
+        .threshold
+        .or(opts.threshold)
+        .unwrap_or(DEFAULT_T_THRESHOLD)
+}
+
+/// Returns the cropping configuration for the given bench. The bench's own configuration takes
+/// precedence over the one in the options.
+fn bench_crop(opts: &BenchOpts, bench: &BenchMetadata) -> CropConfig {
+    bench.crop.clone().unwrap_or_else(|| opts.crop.clone())
 }
 
-fn run_bench_with_bencher(name: &BenchName, benchfn: BenchFn, cb: &mut CtBencher) -> MonitorMsg {
-    let summ = cb.go(benchfn);
+/// Runs a single batch of the given bench and returns the cumulative result, including whether,
+/// and why, the bench should stop
+fn run_batch(
+    opts: &BenchOpts,
+    bench: &BenchMetadata,
+    threshold: f64,
+    cb: &mut CtBencher,
+    env: &mut RunEnv,
+) -> io::Result<BenchReport> {
+    let start = Instant::now();
+    let summary = cb.go(bench.benchfn, &mut env.clock);
 
     // Write the runtime samples out
-    let samples_iter = cb.samples.0.iter().zip(cb.samples.1.iter());
-    if let Some(f) = cb.file_out.as_mut() {
-        for (x, y) in samples_iter {
-            write!(f, "\n{},0,{}", name.0, x).expect("Error writing data to file");
-            write!(f, "\n{},0,{}", name.0, y).expect("Error writing data to file");
-        }
-    };
+    if let Some(f) = env.file_out.as_mut() {
+        f.write_batch(bench.name.0, cb.seed, cb.batches - 1, &cb.samples)?;
+    }
+    cb.elapsed += start.elapsed();
+
+    let ctx = cb
+        .ctx
+        .as_ref()
+        .expect("bench ran without producing statistics");
+    let mut report = BenchReport::new(
+        bench.name, cb.seed, cb.batches, threshold, opts.alpha, summary, ctx,
+    );
+    report.elapsed = cb.elapsed;
+
+    // Check if we've been killed. If so, this batch is the last one
+    report.stop_reason = opts.stop_reason(&report);
+    if report.stop_reason.is_none() && env.run.killed() {
+        report.stop_reason = Some(StopReason::Interrupted);
+    }
+    if report.stop_reason.is_some() {
+        let baseline = env.baselines.get(bench.name.0);
+        report.finalize(ctx, opts.bootstrap.as_ref(), baseline);
+    }
+    cb.stop_reason = report.stop_reason;
+
+    Ok(report)
+}
 
-    (*name, summ)
+/// Returns the final report of a bench that has already stopped
+fn stopped_report(
+    opts: &BenchOpts,
+    bench: &BenchMetadata,
+    threshold: f64,
+    cb: &CtBencher,
+    env: &RunEnv,
+) -> BenchReport {
+    let ctx = cb
+        .ctx
+        .as_ref()
+        .expect("bench stopped without producing statistics");
+    let mut report = BenchReport::new(
+        bench.name,
+        cb.seed,
+        cb.batches,
+        threshold,
+        opts.alpha,
+        ctx.summary(),
+        ctx,
+    );
+    report.elapsed = cb.elapsed;
+    report.stop_reason = cb.stop_reason;
+    let baseline = env.baselines.get(bench.name.0);
+    report.finalize(ctx, opts.bootstrap.as_ref(), baseline);
+    report
 }
 
 fn filter_benches(filter: &Option<String>, bs: Vec<BenchMetadata>) -> Vec<BenchMetadata> {
@@ -323,7 +1166,7 @@ fn filter_benches(filter: &Option<String>, bs: Vec<BenchMetadata>) -> Vec<BenchM
 }
 
 /// Specifies the distribution that a particular run belongs to
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Class {
     Left,
     Right,
@@ -332,8 +1175,9 @@ pub enum Class {
 /// Used for timing single operations at a time
 #[derive(Default)]
 pub struct CtRunner {
-    // Runtimes of left and right distributions in nanoseconds
+    // Runtimes of left and right distributions in the native unit of the timer
     runtimes: (Vec<u64>, Vec<u64>),
+    clock: Clock,
 }
 
 impl CtRunner {
@@ -342,14 +1186,7 @@ impl CtRunner {
     where
         F: Fn() -> T,
     {
-        let start = Instant::now();
-        black_box(f());
-        let end = Instant::now();
-
-        let runtime = {
-            let dur = end.duration_since(start);
-            dur.as_secs() * 1_000_000_000 + u64::from(dur.subsec_nanos())
-        };
+        let runtime = self.clock.measure(f);
 
         match class {
             Class::Left => self.runtimes.0.push(runtime),
@@ -357,3 +1194,61 @@ impl CtRunner {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns a passing report whose test with the max t has `sample_size` samples and measures an
+    /// effect of `max_tau`
+    fn report(sample_size: usize, max_tau: f64) -> BenchReport {
+        BenchReport {
+            name: BenchName("bench"),
+            seed: 0,
+            batches: 1,
+            elapsed: Duration::from_secs(1),
+            summary: CtSummary {
+                max_t: max_tau * (sample_size as f64).sqrt(),
+                max_tau,
+                sample_size,
+                class_sizes: (sample_size / 2, sample_size / 2),
+                ..CtSummary::default()
+            },
+            tests: Vec::new(),
+            threshold: 5f64,
+            alpha: None,
+            verdict: Verdict::Pass,
+            stop_reason: None,
+            bootstrap: None,
+            ctx: None,
+            baseline: None,
+        }
+    }
+
+    #[test]
+    fn adaptive_mode_stops_once_the_verdict_is_settled() {
+        let opts = BenchOpts {
+            continuous: true,
+            min_effect: Some(0.01),
+            ..BenchOpts::default()
+        };
+
+        // A leak of tau = 0.01 is detected with t > 5 after (5/0.01)^2 = 250000 samples
+        let mut leaking = report(1_000, 0.2);
+        leaking.verdict = Verdict::Leak;
+        assert_eq!(opts.stop_reason(&leaking), Some(StopReason::LeakDetected));
+        assert_eq!(
+            opts.stop_reason(&report(250_000, 0.001)),
+            Some(StopReason::EnoughSamples)
+        );
+
+        // Too few samples, counting both classes together, to rule out the minimum effect
+        assert_eq!(opts.stop_reason(&report(249_999, 0.001)), None);
+
+        // Enough samples, but the measured effect is at least the minimum one and, judged by
+        // alpha, isn't significant yet
+        let mut unsettled = report(250_000, 0.02);
+        unsettled.alpha = Some(1e-9);
+        assert_eq!(opts.stop_reason(&unsettled), None);
+    }
+}
@@ -11,15 +11,31 @@
 #![doc = include_str!("../README.md")]
 
 // TODO: More comments
-// TODO: Do "higher order preprocessing" from the paper
 
+pub mod analyze;
+mod baseline;
+mod checkpoint;
+pub mod cli;
+mod csv;
 pub mod ctbench;
 #[doc(hidden)]
 pub mod macros;
+#[cfg(target_os = "linux")]
+mod perf;
+pub mod report;
 mod stats;
+mod timer;
 
 // Re-export the rand dependency
 pub use rand;
 
 #[doc(inline)]
-pub use ctbench::{BenchRng, Class, CtRunner};
+pub use ctbench::{
+    run_benches, BaselineComparison, BenchReport, BenchRng, BootstrapConfig, BootstrapResult,
+    Class, Correction, CropConfig, CropScheme, CtCtx, CtRunner, CtSummary, CtTestResult, DistTest,
+    DistTestResult,
+};
+#[doc(inline)]
+pub use report::{ConsoleReporter, DashboardReporter, JsonReporter, OutputFormat, Reporter};
+#[doc(inline)]
+pub use timer::{PerfEvent, Timer};
@@ -1,7 +1,17 @@
 /// Defines a `fn main()` that will run all benchmarks defined by listed functions `$function` and
 /// their associated seeds (if present). Seeds are represented as `Option<u64>`. If `None` is
 /// given, a random seed will be used. The seeds are used to seed the
-/// [`BenchRng`](crate::ctbench::BenchRng) that's passed to each function.
+/// [`BenchRng`](crate::ctbench::BenchRng) that's passed to each function. They can be overridden
+/// with the `--seed` CLI argument, either for every bench with `--seed HEX`, or for a single bench
+/// with `--seed NAME=HEX`.
+///
+/// Each entry may also give a t-value threshold for its bench as a third element, represented as
+/// `Option<f64>`. A bench whose t-value exceeds its threshold is considered to leak. If `None` is
+/// given, or the element is omitted, the `--threshold` CLI argument is used, which defaults to
+/// [`DEFAULT_T_THRESHOLD`](crate::ctbench::DEFAULT_T_THRESHOLD).
+///
+/// The program exits with status 1 if any bench leaks, and with status 2 if the benchmarks could
+/// not be run.
 ///
 /// ```
 /// use dudect_bencher::{ctbench_main_with_seeds, rand::{Rng, RngExt}, BenchRng, Class, CtRunner};
@@ -24,21 +34,26 @@
 ///
 /// ctbench_main_with_seeds!(
 ///     (foo, None),
-///     (bar, Some(0xdeadbeef))
+///     (bar, Some(0xdeadbeef), Some(10.0))
 /// );
 /// ```
 #[macro_export]
 macro_rules! ctbench_main_with_seeds {
-    ($(($function:path, $seed:expr)),+) => {
+    ($(($function:path, $seed:expr $(, $threshold:expr)?)),+) => {
         use $crate::macros::__macro_internal::{clap::App, PathBuf};
         use $crate::ctbench::{run_benches_console, BenchName, BenchMetadata, BenchOpts};
         fn main() {
+            use $crate::cli::{parse_seed, validate_baseline_name, validate_seed};
+            use $crate::macros::__macro_internal::{clap::Arg, process, Duration};
+
             let mut benches = Vec::new();
             $(
                 benches.push(BenchMetadata {
                     name: BenchName(stringify!($function)),
                     seed: $seed,
                     benchfn: $function,
+                    threshold: $crate::macros::__macro_internal::Option::None$(.or($threshold))?,
+                    crop: $crate::macros::__macro_internal::Option::None,
                 });
             )+
             let matches = App::new("dudect-bencher")
@@ -46,14 +61,218 @@ macro_rules! ctbench_main_with_seeds {
                     "--filter [BENCH] \
                     'Only run the benchmarks whose name contains BENCH'"
                 )
-                .arg_from_usage(
-                    "--continuous [BENCH] \
-                    'Runs a continuous benchmark on the first bench matching BENCH'"
+                .arg(
+                    Arg::from_usage(
+                        "--continuous [BENCH] \
+                        'Continuously runs every bench whose name contains BENCH, or every bench \
+                        if BENCH is not given, taking turns'"
+                    )
+                    .min_values(0)
                 )
                 .arg_from_usage(
                     "--out [FILE] \
                     'Appends raw benchmarking data in CSV format to FILE'"
                 )
+                .arg(
+                    Arg::from_usage(
+                        "--timer [TIMER] \
+                        'Selects the source of timestamps. Defaults to instant'"
+                    )
+                    .possible_values(&["instant", "cycles"])
+                )
+                .arg(
+                    Arg::from_usage(
+                        "--perf-event [EVENT] \
+                        'Counts a hardware event instead of measuring time (Linux only)'"
+                    )
+                    .possible_values(&["instructions", "branch-misses", "l1d-misses"])
+                    .conflicts_with("timer")
+                )
+                .arg(
+                    Arg::from_usage(
+                        "--format [FORMAT] \
+                        'Sets the output format. Defaults to pretty. json prints an array of \
+                        final results only; per-batch results need jsonl'"
+                    )
+                    .possible_values(&["pretty", "json", "jsonl"])
+                )
+                .arg(
+                    Arg::from_usage(
+                        "--threshold [T] \
+                        'Sets the t-value above which a bench is considered to leak, for benches \
+                        that don't set their own. Defaults to 5'"
+                    )
+                    .validator(|s| s.parse::<f64>().map(|_| ()).map_err(|e| e.to_string()))
+                )
+                .arg(
+                    Arg::from_usage(
+                        "--max-time [SECS] \
+                        'Runs each bench repeatedly until it has run for SECS seconds'"
+                    )
+                    .validator(|s| {
+                        s.parse::<f64>()
+                            .map_err(|e| e.to_string())
+                            .and_then(|x| Duration::try_from_secs_f64(x).map_err(|e| e.to_string()))
+                            .map(|_| ())
+                    })
+                )
+                .arg(
+                    Arg::from_usage(
+                        "--max-samples [N] \
+                        'Runs each bench repeatedly until it has collected N samples'"
+                    )
+                    .validator(|s| s.parse::<usize>().map(|_| ()).map_err(|e| e.to_string()))
+                )
+                .arg(
+                    Arg::from_usage(
+                        "--max-iterations [N] \
+                        'Runs each bench repeatedly until its function has been called N times'"
+                    )
+                    .validator(|s| s.parse::<usize>().map(|_| ()).map_err(|e| e.to_string()))
+                )
+                .arg(
+                    Arg::from_usage(
+                        "--min-effect [TAU] \
+                        'Runs each bench until a leak is detected, or until enough samples have \
+                        been collected to detect a leak with effect size TAU'"
+                    )
+                    .validator(|s| match s.parse::<f64>() {
+                        Ok(x) if x > 0.0 => Ok(()),
+                        Ok(_) => Err("TAU must be positive".to_string()),
+                        Err(e) => Err(e.to_string()),
+                    })
+                )
+                .arg(
+                    Arg::from_usage(
+                        "--crop [SCHEME] \
+                        'Sets the percentiles samples are cropped at: exp:N for N exponentially \
+                        spaced ones, linear:N for N evenly spaced ones, a comma-separated list of \
+                        percentiles in (0, 1], or none. Defaults to exp:100'"
+                    )
+                    .validator(|s| {
+                        s.parse::<$crate::CropScheme>().map(|_| ())
+                    })
+                )
+                .arg_from_usage(
+                    "--two-sided-crop \
+                    'Crops both tails of the distributions, keeping the central samples'"
+                )
+                .arg(
+                    Arg::from_usage(
+                        "--crop-reservoir [N] \
+                        'Re-estimates the crop thresholds after 2, 4, 8, ... batches from a random \
+                        sample of up to N of the samples seen so far'"
+                    )
+                    .validator(|s| match s.parse::<usize>() {
+                        Ok(n) if n > 0 => Ok(()),
+                        Ok(_) => Err("N must be positive".to_string()),
+                        Err(e) => Err(e.to_string()),
+                    })
+                )
+                .arg(
+                    Arg::from_usage(
+                        "--dist-tests [TESTS] \
+                        'Runs the given comma-separated nonparametric tests over every sample, in \
+                        addition to the t-tests: ks (Kolmogorov-Smirnov), ad (Anderson-Darling), \
+                        and mw (Mann-Whitney U)'"
+                    )
+                    .validator(|s| {
+                        s.split(',')
+                            .try_for_each(|t| t.parse::<$crate::DistTest>().map(|_| ()))
+                    })
+                )
+                .arg(
+                    Arg::from_usage(
+                        "--correction [METHOD] \
+                        'Sets how the p-values of the t-tests are corrected for multiple \
+                        comparisons. Defaults to holm'"
+                    )
+                    .possible_values(&["none", "bonferroni", "holm"])
+                )
+                .arg(
+                    Arg::from_usage(
+                        "--alpha [P] \
+                        'Judges benches by their corrected p-value instead of their t-values: a \
+                        bench leaks if it is below P'"
+                    )
+                    .validator(|s| match s.parse::<f64>() {
+                        Ok(x) if x > 0.0 && x < 1.0 => Ok(()),
+                        Ok(_) => Err("P must be in (0, 1)".to_string()),
+                        Err(e) => Err(e.to_string()),
+                    })
+                )
+                .arg(
+                    Arg::from_usage(
+                        "--bootstrap [N] \
+                        'Computes bootstrap confidence intervals for the effect size of every \
+                        bench from N resamples, once it finishes'"
+                    )
+                    .validator(|s| match s.parse::<usize>() {
+                        Ok(n) if n > 0 => Ok(()),
+                        Ok(_) => Err("N must be positive".to_string()),
+                        Err(e) => Err(e.to_string()),
+                    })
+                )
+                .arg(
+                    Arg::from_usage(
+                        "--bootstrap-seed [SEED] \
+                        'Seeds the bootstrap with the hex number SEED, rather than the bench seed'"
+                    )
+                    .requires("bootstrap")
+                    .validator(validate_seed)
+                )
+                .arg_from_usage(
+                    "--checkpoint [FILE] \
+                    'Periodically saves the state of every bench to FILE, so that the run can be \
+                    resumed with --resume'"
+                )
+                .arg(
+                    Arg::from_usage(
+                        "--checkpoint-interval [SECS] \
+                        'Sets how often the checkpoint is saved. Defaults to 60'"
+                    )
+                    .validator(|s| {
+                        s.parse::<f64>()
+                            .map_err(|e| e.to_string())
+                            .and_then(|x| Duration::try_from_secs_f64(x).map_err(|e| e.to_string()))
+                            .map(|_| ())
+                    })
+                )
+                .arg_from_usage(
+                    "--resume [FILE] \
+                    'Resumes the run whose state was saved to FILE, and keeps saving it there \
+                    unless --checkpoint is given'"
+                )
+                .arg(
+                    Arg::from_usage(
+                        "--seed [SEED]... \
+                        'Seeds the RNG of every selected bench with the hex number SEED, or, given \
+                        as NAME=SEED, the RNG of the bench NAME. May be given more than once'"
+                    )
+                    .number_of_values(1)
+                    .validator(|s| {
+                        let seed = s.split_once('=').map_or(s.as_str(), |(_, seed)| seed);
+                        parse_seed(seed).map(|_| ())
+                    })
+                )
+                .arg(
+                    Arg::from_usage(
+                        "--save-baseline [NAME] \
+                        'Saves the result of every bench as the baseline NAME once the run ends'"
+                    )
+                    .validator(validate_baseline_name)
+                )
+                .arg(
+                    Arg::from_usage(
+                        "--baseline [NAME] \
+                        'Compares the result of every bench with the baseline NAME'"
+                    )
+                    .validator(validate_baseline_name)
+                )
+                .arg_from_usage(
+                    "--baseline-dir [DIR] \
+                    'Sets the directory that baselines are saved in. Defaults to target/dudect'"
+                )
                 .get_matches();
 
             let mut test_opts = BenchOpts::default();
@@ -63,8 +282,75 @@ macro_rules! ctbench_main_with_seeds {
                 .map(|s| s.to_string());
             test_opts.continuous = matches.is_present("continuous");
             test_opts.file_out = matches.value_of("out").map(PathBuf::from);
+            if let Some(timer) = matches.value_of("timer") {
+                test_opts.timer = timer.parse().unwrap();
+            }
+            if let Some(format) = matches.value_of("format") {
+                test_opts.format = format.parse().unwrap();
+            }
+            test_opts.threshold = matches.value_of("threshold").map(|t| t.parse().unwrap());
+            test_opts.max_time = matches
+                .value_of("max-time")
+                .map(|t| Duration::from_secs_f64(t.parse().unwrap()));
+            test_opts.max_samples = matches.value_of("max-samples").map(|n| n.parse().unwrap());
+            test_opts.max_iterations = matches
+                .value_of("max-iterations")
+                .map(|n| n.parse().unwrap());
+            test_opts.min_effect = matches.value_of("min-effect").map(|t| t.parse().unwrap());
+            if let Some(scheme) = matches.value_of("crop") {
+                test_opts.crop.scheme = scheme.parse().unwrap();
+            }
+            test_opts.crop.two_sided = matches.is_present("two-sided-crop");
+            test_opts.crop.reservoir_size = matches
+                .value_of("crop-reservoir")
+                .map(|n| n.parse().unwrap());
+            if let Some(tests) = matches.value_of("dist-tests") {
+                test_opts.dist_tests = tests.split(',').map(|t| t.parse().unwrap()).collect();
+            }
+            if let Some(correction) = matches.value_of("correction") {
+                test_opts.correction = correction.parse().unwrap();
+            }
+            test_opts.alpha = matches.value_of("alpha").map(|a| a.parse().unwrap());
+            if let Some(n) = matches.value_of("bootstrap") {
+                test_opts.bootstrap = Some($crate::BootstrapConfig {
+                    resamples: n.parse().unwrap(),
+                    seed: matches
+                        .value_of("bootstrap-seed")
+                        .map(|s| parse_seed(s).unwrap()),
+                    ..$crate::BootstrapConfig::default()
+                });
+            }
+            test_opts.checkpoint = matches.value_of("checkpoint").map(PathBuf::from);
+            test_opts.checkpoint_interval = matches
+                .value_of("checkpoint-interval")
+                .map(|t| Duration::from_secs_f64(t.parse().unwrap()));
+            test_opts.resume = matches.value_of("resume").map(PathBuf::from);
+            for seed in matches.values_of("seed").into_iter().flatten() {
+                match seed.split_once('=') {
+                    Some((name, s)) => {
+                        test_opts.bench_seeds.push((name.to_string(), parse_seed(s).unwrap()))
+                    }
+                    None => test_opts.seed = Some(parse_seed(seed).unwrap()),
+                }
+            }
+            test_opts.save_baseline = matches.value_of("save-baseline").map(|s| s.to_string());
+            test_opts.baseline = matches.value_of("baseline").map(|s| s.to_string());
+            test_opts.baseline_dir = matches.value_of("baseline-dir").map(PathBuf::from);
+            if let Some(event) = matches.value_of("perf-event") {
+                test_opts.timer = $crate::Timer::PerfEvent(event.parse().unwrap());
+            }
 
-            run_benches_console(test_opts, benches).unwrap();
+            match run_benches_console(test_opts, benches) {
+                Ok(reports) => {
+                    if reports.iter().any(|r| r.verdict == $crate::ctbench::Verdict::Leak) {
+                        process::exit(1);
+                    }
+                }
+                Err(e) => {
+                    eprintln!("error: {}", e);
+                    process::exit(2);
+                }
+            }
         }
     }
 }
@@ -105,7 +391,7 @@ macro_rules! ctbench_main_with_seeds {
 ///         }
 ///     }
 ///
-///     for (u, class) in inputs.into_iter().zip(classes.into_iter()) {
+///     for (u, class) in inputs.into_iter().zip(classes) {
 ///         // Time some random arithmetic operations
 ///         runner.run_one(class, || ((u + 10) / 6) << 5);
 ///     }
@@ -140,7 +426,7 @@ macro_rules! ctbench_main_with_seeds {
 ///         }
 ///     }
 ///
-///     for (class, (u, v)) in classes.into_iter().zip(inputs.into_iter()) {
+///     for (class, (u, v)) in classes.into_iter().zip(inputs) {
 ///         // Now time how long it takes to do a vector comparison
 ///         runner.run_one(class, || u == v);
 ///     }
@@ -160,5 +446,5 @@ macro_rules! ctbench_main {
 #[doc(hidden)]
 pub mod __macro_internal {
     pub use ::clap;
-    pub use ::std::{option::Option, path::PathBuf};
+    pub use ::std::{option::Option, path::PathBuf, process, time::Duration};
 }
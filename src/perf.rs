@@ -0,0 +1,121 @@
+//! A minimal binding to Linux's `perf_event_open`, used to count hardware events around a single
+//! operation
+
+use crate::timer::PerfEvent;
+
+use std::{
+    fs::File,
+    io::{self, Read},
+    os::fd::FromRawFd,
+};
+
+// Constants from linux/perf_event.h
+const PERF_TYPE_HARDWARE: u32 = 0;
+const PERF_TYPE_HW_CACHE: u32 = 3;
+const PERF_COUNT_HW_INSTRUCTIONS: u64 = 1;
+const PERF_COUNT_HW_BRANCH_MISSES: u64 = 5;
+const PERF_COUNT_HW_CACHE_L1D: u64 = 0;
+const PERF_COUNT_HW_CACHE_OP_READ: u64 = 0;
+const PERF_COUNT_HW_CACHE_RESULT_MISS: u64 = 1;
+const PERF_FLAG_FD_CLOEXEC: libc::c_ulong = 8;
+
+// Bits of the `flags` bitfield of perf_event_attr
+const ATTR_EXCLUDE_KERNEL: u64 = 1 << 5;
+const ATTR_EXCLUDE_HV: u64 = 1 << 6;
+
+/// `struct perf_event_attr`, as of `PERF_ATTR_SIZE_VER5`. Unions are represented by their first
+/// member.
+#[repr(C)]
+#[derive(Default)]
+struct PerfEventAttr {
+    type_: u32,
+    size: u32,
+    config: u64,
+    sample_period: u64,
+    sample_type: u64,
+    read_format: u64,
+    flags: u64,
+    wakeup_events: u32,
+    bp_type: u32,
+    config1: u64,
+    config2: u64,
+    branch_sample_type: u64,
+    sample_regs_user: u64,
+    sample_stack_user: u32,
+    clockid: i32,
+    sample_regs_intr: u64,
+    aux_watermark: u32,
+    sample_max_stack: u16,
+    reserved: u16,
+}
+
+/// An open hardware event counter for the current thread. Only user-space events are counted.
+pub(crate) struct Counter {
+    fd: File,
+}
+
+impl Counter {
+    pub(crate) fn open(event: PerfEvent) -> io::Result<Counter> {
+        let (type_, config) = match event {
+            PerfEvent::Instructions => (PERF_TYPE_HARDWARE, PERF_COUNT_HW_INSTRUCTIONS),
+            PerfEvent::BranchMisses => (PERF_TYPE_HARDWARE, PERF_COUNT_HW_BRANCH_MISSES),
+            PerfEvent::L1dMisses => (
+                PERF_TYPE_HW_CACHE,
+                PERF_COUNT_HW_CACHE_L1D
+                    | (PERF_COUNT_HW_CACHE_OP_READ << 8)
+                    | (PERF_COUNT_HW_CACHE_RESULT_MISS << 16),
+            ),
+        };
+        let attr = PerfEventAttr {
+            type_,
+            size: std::mem::size_of::<PerfEventAttr>() as u32,
+            config,
+            flags: ATTR_EXCLUDE_KERNEL | ATTR_EXCLUDE_HV,
+            ..PerfEventAttr::default()
+        };
+
+        // Count this thread (pid 0) on any CPU (-1), with no group leader (-1)
+        let fd = unsafe {
+            libc::syscall(
+                libc::SYS_perf_event_open,
+                &attr as *const PerfEventAttr,
+                0 as libc::pid_t,
+                -1 as libc::c_int,
+                -1 as libc::c_int,
+                PERF_FLAG_FD_CLOEXEC,
+            )
+        };
+        if fd < 0 {
+            let err = io::Error::last_os_error();
+            let msg = match err.raw_os_error() {
+                Some(libc::EACCES) | Some(libc::EPERM) => format!(
+                    "permission denied opening perf event '{}'. Check \
+                    /proc/sys/kernel/perf_event_paranoid (it must be 2 or lower) or run with \
+                    CAP_PERFMON",
+                    event
+                ),
+                Some(libc::ENOENT) | Some(libc::EOPNOTSUPP) | Some(libc::ENODEV) => format!(
+                    "perf event '{}' is not supported on this machine (this is common inside \
+                    virtual machines and containers)",
+                    event
+                ),
+                Some(libc::ENOSYS) => "perf_event_open is not available on this kernel".to_string(),
+                _ => format!("could not open perf event '{}': {}", event, err),
+            };
+            return Err(io::Error::new(err.kind(), msg));
+        }
+
+        let fd = unsafe { File::from_raw_fd(fd as libc::c_int) };
+        Ok(Counter { fd })
+    }
+
+    /// Returns the current value of the counter
+    #[inline(always)]
+    pub(crate) fn read(&self) -> u64 {
+        let mut buf = [0u8; 8];
+        (&self.fd)
+            .read_exact(&mut buf)
+            .expect("Error reading perf event counter");
+        u64::from_ne_bytes(buf)
+    }
+}
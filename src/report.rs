@@ -0,0 +1,900 @@
+use crate::ctbench::{
+    BaselineComparison, BenchName, BenchReport, BootstrapResult, CtTestResult, DistTestResult,
+    StopReason, Verdict,
+};
+
+use std::{
+    fmt::Write as _,
+    io::{self, IsTerminal, Write},
+    str::FromStr,
+    time::Instant,
+};
+
+/// The format that results are printed to the console in
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Human-readable text, printed by [`ConsoleReporter`]
+    #[default]
+    Pretty,
+    /// A single JSON array of the final record of every bench, printed by [`JsonReporter`] once all
+    /// benches have finished. Intermediate results are left out, so that they aren't buffered.
+    Json,
+    /// One JSON record per line, printed by [`JsonReporter`] as soon as each result is available,
+    /// including the intermediate result of every batch
+    Jsonl,
+}
+
+impl OutputFormat {
+    /// Returns a reporter that prints to the console in this format
+    pub fn reporter(&self) -> Box<dyn Reporter> {
+        match *self {
+            OutputFormat::Pretty => Box::new(ConsoleReporter::new()),
+            OutputFormat::Json => Box::new(JsonReporter::new(false)),
+            OutputFormat::Jsonl => Box::new(JsonReporter::new(true)),
+        }
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<OutputFormat, String> {
+        match s {
+            "pretty" => Ok(OutputFormat::Pretty),
+            "json" => Ok(OutputFormat::Json),
+            "jsonl" => Ok(OutputFormat::Jsonl),
+            _ => Err(format!("unknown output format '{}'", s)),
+        }
+    }
+}
+
+/// Receives the events of a benchmarking run. Every method does nothing by default, so
+/// implementations only need to handle the events they care about.
+///
+/// For every bench, the events are `seed`, then `bench_start`, then zero or more
+/// `intermediate_result`s (one per batch but the last, when a bench is run for more than one
+/// batch), then `bench_finish`. These are preceded by a single `run_start` and `master_seed`, and
+/// followed by a single `run_finish`.
+///
+/// In continuous mode, the benches are run in rounds, so the events of different benches are
+/// interleaved. Every bench's `seed` comes first, then each round runs a batch of every bench that
+/// is still running, and is followed by a `round_finish`.
+///
+/// Several reporters can be used at once by collecting them in a `Vec<Box<dyn Reporter>>`.
+pub trait Reporter {
+    /// Called before any bench is run, with the names of the benches that will be run
+    fn run_start(&mut self, _benches: &[BenchName], _continuous: bool) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Called once the master seed of the run has been chosen. This is the seed of every bench that
+    /// isn't seeded with a seed of its own.
+    fn master_seed(&mut self, _seed: u64) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Called when the seed for a bench's [`BenchRng`](crate::BenchRng)s has been chosen
+    fn seed(&mut self, _name: BenchName, _seed: u64) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Called right before a bench starts running
+    fn bench_start(&mut self, _name: BenchName) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Called after every batch of a bench that runs more than once, with the cumulative result
+    /// so far
+    fn intermediate_result(&mut self, _report: &BenchReport) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Called with the final result of a bench
+    fn bench_finish(&mut self, _report: &BenchReport) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Called in continuous mode after every round, with the latest result of every bench that has
+    /// run so far, including those that have stopped
+    fn round_finish(&mut self, _reports: &[BenchReport]) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Called after all benches have finished, with their final results
+    fn run_finish(&mut self, _reports: &[BenchReport]) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A reporter that ignores every event
+impl Reporter for () {}
+
+impl<R: Reporter + ?Sized> Reporter for &mut R {
+    fn run_start(&mut self, benches: &[BenchName], continuous: bool) -> io::Result<()> {
+        (**self).run_start(benches, continuous)
+    }
+
+    fn master_seed(&mut self, seed: u64) -> io::Result<()> {
+        (**self).master_seed(seed)
+    }
+
+    fn seed(&mut self, name: BenchName, seed: u64) -> io::Result<()> {
+        (**self).seed(name, seed)
+    }
+
+    fn bench_start(&mut self, name: BenchName) -> io::Result<()> {
+        (**self).bench_start(name)
+    }
+
+    fn intermediate_result(&mut self, report: &BenchReport) -> io::Result<()> {
+        (**self).intermediate_result(report)
+    }
+
+    fn bench_finish(&mut self, report: &BenchReport) -> io::Result<()> {
+        (**self).bench_finish(report)
+    }
+
+    fn round_finish(&mut self, reports: &[BenchReport]) -> io::Result<()> {
+        (**self).round_finish(reports)
+    }
+
+    fn run_finish(&mut self, reports: &[BenchReport]) -> io::Result<()> {
+        (**self).run_finish(reports)
+    }
+}
+
+/// Forwards every event to each reporter in turn
+impl Reporter for Vec<Box<dyn Reporter>> {
+    fn run_start(&mut self, benches: &[BenchName], continuous: bool) -> io::Result<()> {
+        self.iter_mut()
+            .try_for_each(|r| r.run_start(benches, continuous))
+    }
+
+    fn master_seed(&mut self, seed: u64) -> io::Result<()> {
+        self.iter_mut().try_for_each(|r| r.master_seed(seed))
+    }
+
+    fn seed(&mut self, name: BenchName, seed: u64) -> io::Result<()> {
+        self.iter_mut().try_for_each(|r| r.seed(name, seed))
+    }
+
+    fn bench_start(&mut self, name: BenchName) -> io::Result<()> {
+        self.iter_mut().try_for_each(|r| r.bench_start(name))
+    }
+
+    fn intermediate_result(&mut self, report: &BenchReport) -> io::Result<()> {
+        self.iter_mut()
+            .try_for_each(|r| r.intermediate_result(report))
+    }
+
+    fn bench_finish(&mut self, report: &BenchReport) -> io::Result<()> {
+        self.iter_mut().try_for_each(|r| r.bench_finish(report))
+    }
+
+    fn round_finish(&mut self, reports: &[BenchReport]) -> io::Result<()> {
+        self.iter_mut().try_for_each(|r| r.round_finish(reports))
+    }
+
+    fn run_finish(&mut self, reports: &[BenchReport]) -> io::Result<()> {
+        self.iter_mut().try_for_each(|r| r.run_finish(reports))
+    }
+}
+
+/// The default reporter, which prints human-readable results to stdout. When several benches are
+/// run continuously, a table of every bench's current max t is printed after each round, instead of
+/// a line per batch.
+#[derive(Default)]
+pub struct ConsoleReporter {
+    // Number of columns to fill when aligning names
+    max_name_len: usize,
+    // Whether a "bench foo ... " line has been started but not finished
+    line_open: bool,
+    // Number of benches the run was started with
+    bench_count: usize,
+    // Whether several benches are being run continuously, so that results are printed as a table
+    round_robin: bool,
+    // Number of rounds finished so far
+    rounds: usize,
+    // The master seed of the run, which is only printed once
+    master_seed: Option<u64>,
+}
+
+impl ConsoleReporter {
+    pub fn new() -> ConsoleReporter {
+        ConsoleReporter::default()
+    }
+
+    fn write_plain(&mut self, s: &str) -> io::Result<()> {
+        let mut stdout = io::stdout();
+        stdout.write_all(s.as_bytes())?;
+        stdout.flush()
+    }
+
+    fn write_result(&mut self, report: &BenchReport) -> io::Result<()> {
+        if !self.line_open {
+            let name = report.name.padded(self.max_name_len);
+            self.write_plain(&format!("bench {} ... ", name))?;
+        }
+        self.line_open = false;
+        self.write_plain(&format!("{}: {}\n", report.verdict, report.summary.fmt()))
+    }
+}
+
+impl Reporter for ConsoleReporter {
+    fn run_start(&mut self, benches: &[BenchName], continuous: bool) -> io::Result<()> {
+        self.max_name_len = benches.iter().map(|b| b.0.len()).max().unwrap_or(0);
+        self.bench_count = benches.len();
+        self.round_robin = continuous && benches.len() > 1;
+        if continuous {
+            let len = benches.len();
+            let noun = if len != 1 { "benchmarks" } else { "benchmark" };
+            self.write_plain(&format!("running {} {} continuously\n", len, noun))
+        } else {
+            let len = benches.len();
+            let noun = if len != 1 { "benches" } else { "bench" };
+            self.write_plain(&format!("\nrunning {} {}\n", len, noun))
+        }
+    }
+
+    fn master_seed(&mut self, seed: u64) -> io::Result<()> {
+        self.master_seed = Some(seed);
+        self.write_plain(&format!("master seed 0x{:016x}\n", seed))
+    }
+
+    fn seed(&mut self, name: BenchName, seed: u64) -> io::Result<()> {
+        // Benches seeded with the master seed were covered when it was printed
+        if self.master_seed == Some(seed) {
+            return Ok(());
+        }
+        let name = name.padded(self.max_name_len);
+        self.write_plain(&format!("bench {} seeded with 0x{:016x}\n", name, seed))
+    }
+
+    fn bench_start(&mut self, name: BenchName) -> io::Result<()> {
+        if self.round_robin {
+            return Ok(());
+        }
+        let name = name.padded(self.max_name_len);
+        self.line_open = true;
+        self.write_plain(&format!("bench {} ... ", name))
+    }
+
+    fn intermediate_result(&mut self, report: &BenchReport) -> io::Result<()> {
+        if self.round_robin {
+            return Ok(());
+        }
+        self.write_result(report)
+    }
+
+    fn bench_finish(&mut self, report: &BenchReport) -> io::Result<()> {
+        self.write_result(report)?;
+        if let Some(ref b) = report.bootstrap {
+            let name = report.name.padded(self.max_name_len);
+            self.write_plain(&format!(
+                "bench {} {}% bootstrap intervals ({} resamples, seed 0x{:016x}): mean diff = \
+                [{:+0.5}, {:+0.5}], tau = [{:+0.5}, {:+0.5}], (5/tau)^2 = [{}, {}]\n",
+                name,
+                100f64 * b.confidence,
+                b.resamples,
+                b.seed,
+                b.mean_diff.0,
+                b.mean_diff.1,
+                b.tau.0,
+                b.tau.1,
+                b.needed_samples.0 as usize,
+                if b.needed_samples.1.is_finite() {
+                    (b.needed_samples.1 as usize).to_string()
+                } else {
+                    "inf".to_string()
+                },
+            ))?;
+        }
+        if let Some(ref b) = report.baseline {
+            let name = report.name.padded(self.max_name_len);
+            self.write_plain(&format!(
+                "bench {} vs baseline '{}': |max t| = {:0.5} ({:+0.5}), |max tau| = {:0.5} \
+                ({:+0.5}), was {}\n",
+                name,
+                b.baseline,
+                report.summary.max_t.abs(),
+                b.max_t_delta,
+                report.summary.max_tau.abs(),
+                b.max_tau_delta,
+                b.verdict
+            ))?;
+        }
+        match report.stop_reason {
+            None | Some(StopReason::SingleRun) => Ok(()),
+            Some(reason) => {
+                let name = report.name.padded(self.max_name_len);
+                let noun = if report.batches != 1 {
+                    "batches"
+                } else {
+                    "batch"
+                };
+                let (left, right) = report.summary.class_sizes;
+                self.write_plain(&format!(
+                    "bench {} stopped after {} {} ({} samples) in {:.2}s: {}\n",
+                    name,
+                    report.batches,
+                    noun,
+                    left + right,
+                    report.elapsed.as_secs_f64(),
+                    reason
+                ))
+            }
+        }
+    }
+
+    fn round_finish(&mut self, reports: &[BenchReport]) -> io::Result<()> {
+        self.rounds += 1;
+        if !self.round_robin {
+            return Ok(());
+        }
+
+        let mut s = format!("\nafter round {}:\n", self.rounds);
+        for r in reports {
+            let status = if r.stop_reason.is_some() {
+                "stopped"
+            } else {
+                "running"
+            };
+            let noun = if r.batches != 1 { "batches" } else { "batch" };
+            s.push_str(&format!(
+                "    {} {}: max t = {:+0.5}, n == {:+0.3}M, {} {}, {}\n",
+                r.name.padded(self.max_name_len),
+                r.verdict,
+                r.summary.max_t,
+                (r.summary.sample_size as f64) / 1_000_000f64,
+                r.batches,
+                noun,
+                status
+            ));
+        }
+        self.write_plain(&s)
+    }
+
+    fn run_finish(&mut self, reports: &[BenchReport]) -> io::Result<()> {
+        let interrupted = reports.len() < self.bench_count
+            || reports
+                .iter()
+                .any(|r| r.stop_reason == Some(StopReason::Interrupted));
+        if interrupted {
+            self.write_plain(&format!(
+                "\ndudect benches interrupted ({} of {} run)\n\n",
+                reports.len(),
+                self.bench_count
+            ))?;
+        } else {
+            self.write_plain("\ndudect benches complete\n\n")?;
+        }
+
+        let leaks: Vec<&BenchReport> = reports
+            .iter()
+            .filter(|r| r.verdict == Verdict::Leak)
+            .collect();
+        if !leaks.is_empty() {
+            let noun = if leaks.len() != 1 { "benches" } else { "bench" };
+            let mut s = format!("{} {} leaked:\n", leaks.len(), noun);
+            for r in leaks {
+                let detail = match r.alpha {
+                    Some(alpha) => format!(
+                        "corrected p = {:0.3e}, alpha = {}",
+                        r.summary.corrected_p_value, alpha
+                    ),
+                    None => format!(
+                        "max t = {:+0.5}, threshold = {}",
+                        r.summary.max_t, r.threshold
+                    ),
+                };
+                s.push_str(&format!(
+                    "    {} ({})\n",
+                    r.name.padded(self.max_name_len),
+                    detail
+                ));
+            }
+            s.push('\n');
+            self.write_plain(&s)?;
+        }
+
+        let regressions: Vec<&BenchReport> = reports
+            .iter()
+            .filter(|r| r.baseline.as_ref().is_some_and(|b| b.regressed))
+            .collect();
+        if !regressions.is_empty() {
+            let noun = if regressions.len() != 1 {
+                "benches"
+            } else {
+                "bench"
+            };
+            let mut s = format!(
+                "{} {} went from PASS to LEAK since the baseline:\n",
+                regressions.len(),
+                noun
+            );
+            for r in regressions {
+                let b = r.baseline.as_ref().unwrap();
+                s.push_str(&format!(
+                    "    {} (max t = {:+0.5}, was {:+0.5} in '{}')\n",
+                    r.name.padded(self.max_name_len),
+                    r.summary.max_t,
+                    b.max_t,
+                    b.baseline
+                ));
+            }
+            s.push('\n');
+            self.write_plain(&s)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The number of past max t values shown in the [`DashboardReporter`]'s sparklines
+const SPARKLINE_LEN: usize = 32;
+
+/// A reporter for continuous runs that keeps a table of every bench's current state on screen,
+/// updating it in place after every round. For each bench, it shows the number of samples per
+/// class, the current max t and tau, which percentile crop produced the max t, the time spent on
+/// the bench, and a sparkline of |max t| over the last few rounds.
+///
+/// In-place updates use ANSI escape codes, so they are only done when stdout is a terminal.
+/// Otherwise, this prints the same output as [`ConsoleReporter`].
+pub struct DashboardReporter {
+    // Prints the header, seeds, and final summary, as well as everything when not in a terminal
+    console: ConsoleReporter,
+    // Whether to draw the table in place
+    ansi: bool,
+    rows: Vec<DashboardRow>,
+    // Number of lines the table took up when it was last drawn
+    lines_drawn: usize,
+    start: Instant,
+}
+
+/// The state of a single bench in the [`DashboardReporter`]
+struct DashboardRow {
+    name: BenchName,
+    latest: Option<BenchReport>,
+    // |max t| after each of the last `SPARKLINE_LEN` batches
+    history: Vec<f64>,
+}
+
+impl DashboardReporter {
+    /// Makes a dashboard that draws in place if stdout is a terminal
+    pub fn new() -> DashboardReporter {
+        DashboardReporter {
+            console: ConsoleReporter::new(),
+            ansi: io::stdout().is_terminal(),
+            rows: Vec::new(),
+            lines_drawn: 0,
+            start: Instant::now(),
+        }
+    }
+
+    fn update(&mut self, report: &BenchReport) {
+        let Some(row) = self.rows.iter_mut().find(|r| r.name == report.name) else {
+            return;
+        };
+        // An interrupted bench is finished with the report of a batch that was already seen
+        let is_new_batch = row
+            .latest
+            .as_ref()
+            .is_none_or(|r| r.batches != report.batches);
+        if is_new_batch {
+            if row.history.len() == SPARKLINE_LEN {
+                row.history.remove(0);
+            }
+            row.history.push(report.summary.max_t.abs());
+        }
+        row.latest = Some(report.clone());
+    }
+
+    /// Redraws the table over the previous one. `done` is whether the run has finished.
+    fn draw(&mut self, done: bool) -> io::Result<()> {
+        let name_len = self.rows.iter().map(|r| r.name.0.len()).max().unwrap_or(0);
+        let mut s = String::new();
+        if self.lines_drawn > 0 {
+            // Move to the start of the old table and clear everything after it
+            write!(s, "\x1b[{}F\x1b[J", self.lines_drawn).unwrap();
+        }
+
+        let hint = if done { "done" } else { "press Ctrl-C to stop" };
+        writeln!(
+            s,
+            "\nelapsed {:.1}s, {}\n",
+            self.start.elapsed().as_secs_f64(),
+            hint
+        )
+        .unwrap();
+        writeln!(
+            s,
+            "{}  {:>9}  {:>9}  {:>12}  {:>9}  {:>7}  {:>8}  {:<4}  {:<w$}  status",
+            BenchName("bench").padded(name_len),
+            "left",
+            "right",
+            "max t",
+            "max tau",
+            "crop",
+            "time",
+            "",
+            "|max t| history",
+            w = SPARKLINE_LEN,
+        )
+        .unwrap();
+        for row in &self.rows {
+            let name = row.name.padded(name_len);
+            let Some(r) = row.latest.as_ref() else {
+                writeln!(s, "{}  waiting", name).unwrap();
+                continue;
+            };
+            let summ = &r.summary;
+            let crop = match r.tests.get(summ.max_t_index).and_then(|t| t.percentile) {
+                Some(pct) => format!("p={:.3}", pct),
+                None => "none".to_string(),
+            };
+            let status = r
+                .stop_reason
+                .map_or("running".to_string(), |reason| reason.to_string());
+            writeln!(
+                s,
+                "{}  {:>9}  {:>9}  {:>+12.5}  {:>+9.5}  {:>7}  {:>7.1}s  {:<4}  {:<w$}  {}",
+                name,
+                summ.class_sizes.0,
+                summ.class_sizes.1,
+                summ.max_t,
+                summ.max_tau,
+                crop,
+                r.elapsed.as_secs_f64(),
+                r.verdict,
+                sparkline(&row.history),
+                status,
+                w = SPARKLINE_LEN,
+            )
+            .unwrap();
+        }
+
+        self.lines_drawn = s.matches('\n').count();
+        let mut stdout = io::stdout();
+        stdout.write_all(s.as_bytes())?;
+        stdout.flush()
+    }
+}
+
+impl Default for DashboardReporter {
+    fn default() -> DashboardReporter {
+        DashboardReporter::new()
+    }
+}
+
+impl Reporter for DashboardReporter {
+    fn run_start(&mut self, benches: &[BenchName], continuous: bool) -> io::Result<()> {
+        self.start = Instant::now();
+        self.rows = benches
+            .iter()
+            .map(|&name| DashboardRow {
+                name,
+                latest: None,
+                history: Vec::new(),
+            })
+            .collect();
+        self.console.run_start(benches, continuous)
+    }
+
+    fn master_seed(&mut self, seed: u64) -> io::Result<()> {
+        self.console.master_seed(seed)
+    }
+
+    fn seed(&mut self, name: BenchName, seed: u64) -> io::Result<()> {
+        self.console.seed(name, seed)
+    }
+
+    fn bench_start(&mut self, name: BenchName) -> io::Result<()> {
+        if self.ansi {
+            return Ok(());
+        }
+        self.console.bench_start(name)
+    }
+
+    fn intermediate_result(&mut self, report: &BenchReport) -> io::Result<()> {
+        if self.ansi {
+            self.update(report);
+            return Ok(());
+        }
+        self.console.intermediate_result(report)
+    }
+
+    fn bench_finish(&mut self, report: &BenchReport) -> io::Result<()> {
+        if self.ansi {
+            self.update(report);
+            return Ok(());
+        }
+        self.console.bench_finish(report)
+    }
+
+    fn round_finish(&mut self, reports: &[BenchReport]) -> io::Result<()> {
+        if self.ansi {
+            return self.draw(false);
+        }
+        self.console.round_finish(reports)
+    }
+
+    fn run_finish(&mut self, reports: &[BenchReport]) -> io::Result<()> {
+        if self.ansi {
+            self.draw(true)?;
+        }
+        self.console.run_finish(reports)
+    }
+}
+
+/// Draws the given non-negative values as a sparkline, scaled so that the largest is a full block
+fn sparkline(values: &[f64]) -> String {
+    const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    let max = values.iter().cloned().fold(0f64, f64::max);
+    values
+        .iter()
+        .map(|&x| {
+            if max > 0.0 && x.is_finite() {
+                BARS[((x / max) * 7.0).round() as usize]
+            } else {
+                BARS[0]
+            }
+        })
+        .collect()
+}
+
+/// A reporter that prints a machine-readable JSON record to stdout for every result. When printing
+/// JSON lines, this includes the intermediate results of benches that run for more than one batch.
+/// When printing a single array at the end of the run, only final results are included, so that a
+/// long continuous run doesn't accumulate a record for every batch in memory. Each record is an
+/// object with the fields
+///
+/// * `name`: the name of the bench
+/// * `seed`: the seed of the bench's RNG, as a hex string
+/// * `batches`: the number of batches that went into the result
+/// * `elapsed`: the time spent running the bench so far, in seconds
+/// * `final`: whether this is the final result of the bench
+/// * `samples`: an object with the number of `left` and `right` samples collected
+/// * `max_t`, `max_tau`: the t-value with the largest magnitude, and its tau
+/// * `max_t_index`: the index of the test that produced `max_t`, where 0 is the uncropped test
+///   and `k > 0` is the test with the `k`-th crop
+/// * `needed_samples`: `(5/tau)^2`
+/// * `max_t_df`, `max_t_p_value`: the Welch–Satterthwaite degrees of freedom and the uncorrected
+///   two-sided p-value of `max_t`
+/// * `second_order_t`, `second_order_p_value`: the t-value of the second-order test and its
+///   uncorrected p-value
+/// * `corrected_p_value`: the smallest p-value of every t-test, after correcting for multiple
+///   comparisons
+/// * `ks`, `anderson_darling`, `mann_whitney`: the results of the nonparametric tests, as objects
+///   with a `statistic` and a `p_value`, or `null` if the test isn't enabled
+/// * `threshold`: the t-value threshold the bench was judged against
+/// * `alpha`: the significance level the bench was judged at instead, if any
+/// * `verdict`: `"PASS"` or `"LEAK"`
+/// * `bootstrap`: the bootstrap confidence intervals of final results, as an object with the
+///   number of `resamples`, the `confidence` level, the `seed` as a hex string, and the
+///   `mean_diff`, `tau`, and `needed_samples` intervals as two-element arrays, or `null` if there
+///   are none
+/// * `baseline`: for final results, how the bench compares with its result in the baseline, as an
+///   object with the `name` of the baseline, the `max_t`, `max_tau`, and `verdict` in the
+///   baseline, the changes `max_t_delta` and `max_tau_delta` in their magnitudes, and whether the
+///   bench `regressed` from `"PASS"` to `"LEAK"`, or `null` if there is no baseline
+/// * `tests`: for final results, an array with the details of every t-test, uncropped first,
+///   followed by each crop. Each is an object with the crop `percentile`, the crop `threshold` and
+///   `lower_threshold` in timer units, `samples`, `means`, and `variances`, each an object with a
+///   `left` and `right` value, and `t`, `df`, `p_value`, and `corrected_p_value`. The values that
+///   don't apply to a test are `null`. This is `null` for intermediate results.
+/// * `stop_reason`: why the bench stopped, as described by [`StopReason`]'s `Display`, or `null`
+///   for intermediate results
+///
+/// Numbers that are not finite, and values that are unavailable, are written as `null`.
+pub struct JsonReporter {
+    // Whether to print each record on its own line as it arrives, rather than an array at the end
+    lines: bool,
+    records: Vec<String>,
+}
+
+impl JsonReporter {
+    /// Makes a reporter printing JSON lines if `lines` is set, and a single JSON array of final
+    /// results otherwise
+    pub fn new(lines: bool) -> JsonReporter {
+        JsonReporter {
+            lines,
+            records: Vec::new(),
+        }
+    }
+
+    fn write_record(&mut self, report: &BenchReport, is_final: bool) -> io::Result<()> {
+        if self.lines {
+            let mut stdout = io::stdout();
+            writeln!(stdout, "{}", json_record(report, is_final))?;
+            stdout.flush()
+        } else {
+            // Intermediate results are superseded by the final one, so there's no point keeping
+            // them around until the end
+            if is_final {
+                self.records.push(json_record(report, is_final));
+            }
+            Ok(())
+        }
+    }
+}
+
+impl Reporter for JsonReporter {
+    fn intermediate_result(&mut self, report: &BenchReport) -> io::Result<()> {
+        self.write_record(report, false)
+    }
+
+    fn bench_finish(&mut self, report: &BenchReport) -> io::Result<()> {
+        self.write_record(report, true)
+    }
+
+    fn run_finish(&mut self, _reports: &[BenchReport]) -> io::Result<()> {
+        if self.lines {
+            return Ok(());
+        }
+
+        let mut stdout = io::stdout();
+        writeln!(stdout, "[")?;
+        for (i, record) in self.records.iter().enumerate() {
+            let sep = if i + 1 < self.records.len() { "," } else { "" };
+            writeln!(stdout, "  {}{}", record, sep)?;
+        }
+        writeln!(stdout, "]")?;
+        stdout.flush()
+    }
+}
+
+/// Returns the JSON record describing the given result
+fn json_record(report: &BenchReport, is_final: bool) -> String {
+    let summ = &report.summary;
+    let mut s = String::new();
+    write!(
+        s,
+        "{{\"name\":{},\"seed\":\"0x{:016x}\",\"batches\":{},\"elapsed\":{},\"final\":{},\
+        \"samples\":{{\"left\":{},\"right\":{}}},\"max_t\":{},\"max_tau\":{},\
+        \"max_t_index\":{},\"max_t_df\":{},\"max_t_p_value\":{},\"needed_samples\":{},\
+        \"second_order_t\":{},\"second_order_p_value\":{},\"corrected_p_value\":{},\
+        \"ks\":{},\"anderson_darling\":{},\"mann_whitney\":{},\"threshold\":{},\"alpha\":{},\
+        \"verdict\":\"{}\",\"stop_reason\":{},\"bootstrap\":{},\"baseline\":{},\"tests\":{}}}",
+        json_str(report.name.0),
+        report.seed,
+        report.batches,
+        json_num(report.elapsed.as_secs_f64()),
+        is_final,
+        summ.class_sizes.0,
+        summ.class_sizes.1,
+        json_num(summ.max_t),
+        json_num(summ.max_tau),
+        summ.max_t_index,
+        json_num(summ.max_t_df),
+        json_num(summ.max_t_p_value),
+        json_num(summ.needed_samples()),
+        summ.second_order_t.map_or("null".to_string(), json_num),
+        summ.second_order_p_value
+            .map_or("null".to_string(), json_num),
+        json_num(summ.corrected_p_value),
+        json_dist_test(summ.ks),
+        json_dist_test(summ.anderson_darling),
+        json_dist_test(summ.mann_whitney),
+        json_num(report.threshold),
+        report.alpha.map_or("null".to_string(), json_num),
+        report.verdict,
+        report
+            .stop_reason
+            .map_or("null".to_string(), |r| json_str(&r.to_string())),
+        json_bootstrap(report.bootstrap.as_ref()),
+        json_baseline(report.baseline.as_ref()),
+        if is_final {
+            json_tests(&report.tests)
+        } else {
+            "null".to_string()
+        },
+    )
+    .unwrap();
+
+    s
+}
+
+/// Encodes the result of a nonparametric test as a JSON object, or `null` if there is none
+fn json_dist_test(res: Option<DistTestResult>) -> String {
+    res.map_or("null".to_string(), |r| {
+        format!(
+            "{{\"statistic\":{},\"p_value\":{}}}",
+            json_num(r.statistic),
+            json_num(r.p_value)
+        )
+    })
+}
+
+/// Encodes the details of every t-test as a JSON array
+fn json_tests(tests: &[CtTestResult]) -> String {
+    let pair = |(left, right): (f64, f64)| {
+        format!(
+            "{{\"left\":{},\"right\":{}}}",
+            json_num(left),
+            json_num(right)
+        )
+    };
+    let opt = |x: Option<f64>| x.map_or("null".to_string(), json_num);
+    let records: Vec<String> = tests
+        .iter()
+        .map(|t| {
+            format!(
+                "{{\"percentile\":{},\"threshold\":{},\"lower_threshold\":{},\
+                \"samples\":{{\"left\":{},\"right\":{}}},\"means\":{},\"variances\":{},\"t\":{},\
+                \"df\":{},\"p_value\":{},\"corrected_p_value\":{}}}",
+                opt(t.percentile),
+                opt(t.threshold),
+                opt(t.lower_threshold),
+                t.sample_sizes.0,
+                t.sample_sizes.1,
+                pair(t.means),
+                pair(t.variances),
+                json_num(t.t),
+                json_num(t.df),
+                json_num(t.p_value),
+                json_num(t.corrected_p_value)
+            )
+        })
+        .collect();
+
+    format!("[{}]", records.join(","))
+}
+
+/// Encodes bootstrap confidence intervals as a JSON object, or `null` if there are none
+fn json_bootstrap(res: Option<&BootstrapResult>) -> String {
+    let interval = |(lo, hi): (f64, f64)| format!("[{},{}]", json_num(lo), json_num(hi));
+    res.map_or("null".to_string(), |b| {
+        format!(
+            "{{\"resamples\":{},\"confidence\":{},\"seed\":\"0x{:016x}\",\"mean_diff\":{},\
+            \"tau\":{},\"needed_samples\":{}}}",
+            b.resamples,
+            json_num(b.confidence),
+            b.seed,
+            interval(b.mean_diff),
+            interval(b.tau),
+            interval(b.needed_samples)
+        )
+    })
+}
+
+/// Encodes a comparison with a baseline as a JSON object, or `null` if there is none
+fn json_baseline(res: Option<&BaselineComparison>) -> String {
+    res.map_or("null".to_string(), |b| {
+        format!(
+            "{{\"name\":{},\"max_t\":{},\"max_tau\":{},\"verdict\":\"{}\",\"max_t_delta\":{},\
+            \"max_tau_delta\":{},\"regressed\":{}}}",
+            json_str(&b.baseline),
+            json_num(b.max_t),
+            json_num(b.max_tau),
+            b.verdict,
+            json_num(b.max_t_delta),
+            json_num(b.max_tau_delta),
+            b.regressed
+        )
+    })
+}
+
+/// Encodes a float as a JSON number, or `null` if it isn't finite
+fn json_num(x: f64) -> String {
+    if x.is_finite() {
+        format!("{}", x)
+    } else {
+        "null".to_string()
+    }
+}
+
+/// Encodes a string as a JSON string
+fn json_str(x: &str) -> String {
+    let mut s = String::from("\"");
+    for c in x.chars() {
+        match c {
+            '"' => s.push_str("\\\""),
+            '\\' => s.push_str("\\\\"),
+            c if (c as u32) < 0x20 => write!(s, "\\u{:04x}", c as u32).unwrap(),
+            c => s.push(c),
+        }
+    }
+    s.push('"');
+
+    s
+}
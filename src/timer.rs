@@ -0,0 +1,247 @@
+#[cfg(target_os = "linux")]
+use crate::perf;
+
+use std::{fmt, hint::black_box, io, str::FromStr, time::Instant};
+
+/// The source of timestamps used by [`CtRunner::run_one`](crate::CtRunner::run_one). Samples are
+/// recorded in the native unit of the timer, given by [`Timer::unit`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Timer {
+    /// [`std::time::Instant`]. This works everywhere, but on most platforms it has tens of
+    /// nanoseconds of overhead and jitter, which can drown out leaks of a few cycles.
+    #[default]
+    Instant,
+    /// The CPU's cycle counter. This is `rdtsc`/`rdtscp` with fences on x86_64 and `cntvct_el0`
+    /// on aarch64. On any other architecture this falls back to [`Timer::Instant`].
+    CycleCounter,
+    /// A hardware event counter, read with Linux's `perf_event_open`. This counts events rather
+    /// than time, which is far less noisy. This is only available on Linux, and benchmarks fail to
+    /// start if the counter cannot be opened.
+    PerfEvent(PerfEvent),
+}
+
+/// A hardware event that can be counted by [`Timer::PerfEvent`]. Only events that happen in user
+/// space are counted.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PerfEvent {
+    /// Retired instructions
+    Instructions,
+    /// Mispredicted branches
+    BranchMisses,
+    /// L1 data cache read misses
+    L1dMisses,
+}
+
+impl Timer {
+    /// Returns whether this timer is available on the current architecture
+    pub fn is_supported(&self) -> bool {
+        match *self {
+            Timer::Instant => true,
+            Timer::CycleCounter => cfg!(any(target_arch = "x86_64", target_arch = "aarch64")),
+            Timer::PerfEvent(_) => cfg!(target_os = "linux"),
+        }
+    }
+
+    /// Returns the timer that will actually be used. This is `self`, unless `self` is an
+    /// unsupported [`Timer::CycleCounter`], in which case it is [`Timer::Instant`].
+    pub fn resolve(self) -> Timer {
+        match self {
+            Timer::CycleCounter if !self.is_supported() => Timer::Instant,
+            t => t,
+        }
+    }
+
+    /// Returns the unit that samples taken with this timer are recorded in
+    pub fn unit(&self) -> &'static str {
+        match self.resolve() {
+            Timer::Instant => "ns",
+            Timer::CycleCounter if cfg!(target_arch = "aarch64") => "ticks",
+            Timer::CycleCounter => "cycles",
+            Timer::PerfEvent(e) => e.name(),
+        }
+    }
+}
+
+impl PerfEvent {
+    /// Returns the name of the event, which is also the unit of samples counting it
+    pub fn name(&self) -> &'static str {
+        match *self {
+            PerfEvent::Instructions => "instructions",
+            PerfEvent::BranchMisses => "branch-misses",
+            PerfEvent::L1dMisses => "l1d-misses",
+        }
+    }
+}
+
+impl fmt::Display for PerfEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for PerfEvent {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<PerfEvent, String> {
+        match s {
+            "instructions" => Ok(PerfEvent::Instructions),
+            "branch-misses" => Ok(PerfEvent::BranchMisses),
+            "l1d-misses" => Ok(PerfEvent::L1dMisses),
+            _ => Err(format!("unknown perf event '{}'", s)),
+        }
+    }
+}
+
+/// An instantiated [`Timer`], holding whatever state it needs to take measurements
+#[derive(Default)]
+pub(crate) enum Clock {
+    #[default]
+    Instant,
+    CycleCounter,
+    #[cfg(target_os = "linux")]
+    PerfEvent(perf::Counter),
+}
+
+impl Clock {
+    /// Sets up the given timer. This fails if the timer is a perf event counter that can't be
+    /// opened.
+    pub(crate) fn new(timer: Timer) -> io::Result<Clock> {
+        match timer.resolve() {
+            Timer::Instant => Ok(Clock::Instant),
+            Timer::CycleCounter => Ok(Clock::CycleCounter),
+            #[cfg(target_os = "linux")]
+            Timer::PerfEvent(e) => perf::Counter::open(e).map(Clock::PerfEvent),
+            #[cfg(not(target_os = "linux"))]
+            Timer::PerfEvent(_) => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "perf event counters are only supported on Linux",
+            )),
+        }
+    }
+
+    /// Runs `f` and returns how long it took, in the unit given by [`Timer::unit`]
+    #[inline(always)]
+    pub(crate) fn measure<T, F>(&self, f: F) -> u64
+    where
+        F: Fn() -> T,
+    {
+        match self {
+            Clock::Instant => {
+                let start = Instant::now();
+                black_box(f());
+                let end = Instant::now();
+
+                let dur = end.duration_since(start);
+                dur.as_secs() * 1_000_000_000 + u64::from(dur.subsec_nanos())
+            }
+            Clock::CycleCounter => {
+                let start = cycles::start();
+                black_box(f());
+                let end = cycles::stop();
+
+                end.wrapping_sub(start)
+            }
+            #[cfg(target_os = "linux")]
+            Clock::PerfEvent(counter) => {
+                let start = counter.read();
+                black_box(f());
+                let end = counter.read();
+
+                end.wrapping_sub(start)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Timer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Timer::Instant => f.write_str("instant"),
+            Timer::CycleCounter => f.write_str("cycles"),
+            Timer::PerfEvent(e) => write!(f, "perf:{}", e),
+        }
+    }
+}
+
+impl FromStr for Timer {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Timer, String> {
+        match s {
+            "instant" => Ok(Timer::Instant),
+            "cycles" => Ok(Timer::CycleCounter),
+            _ => match s.strip_prefix("perf:") {
+                Some(e) => e.parse().map(Timer::PerfEvent),
+                None => Err(format!("unknown timer '{}'", s)),
+            },
+        }
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+#[allow(unused_unsafe)]
+mod cycles {
+    use core::arch::x86_64::{__rdtscp, _mm_lfence, _rdtsc};
+
+    /// Reads the TSC. The fences keep earlier instructions from finishing after the read, and
+    /// the timed code from starting before it.
+    #[inline(always)]
+    pub(super) fn start() -> u64 {
+        unsafe {
+            _mm_lfence();
+            let t = _rdtsc();
+            _mm_lfence();
+            t
+        }
+    }
+
+    /// Reads the TSC. `rdtscp` waits for the timed code to finish, and the fence keeps later
+    /// instructions from starting before the read.
+    #[inline(always)]
+    pub(super) fn stop() -> u64 {
+        let mut aux = 0u32;
+        unsafe {
+            let t = __rdtscp(&mut aux);
+            _mm_lfence();
+            t
+        }
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod cycles {
+    use core::arch::asm;
+
+    /// Reads the virtual counter. The `isb` keeps the read from being reordered with respect to
+    /// the surrounding instructions.
+    #[inline(always)]
+    fn read() -> u64 {
+        let t: u64;
+        unsafe {
+            asm!("isb", "mrs {}, cntvct_el0", "isb", out(reg) t, options(nostack));
+        }
+        t
+    }
+
+    #[inline(always)]
+    pub(super) fn start() -> u64 {
+        read()
+    }
+
+    #[inline(always)]
+    pub(super) fn stop() -> u64 {
+        read()
+    }
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+mod cycles {
+    // Never called, since `Timer::resolve` falls back to `Timer::Instant` on these platforms
+    pub(super) fn start() -> u64 {
+        unreachable!()
+    }
+
+    pub(super) fn stop() -> u64 {
+        unreachable!()
+    }
+}